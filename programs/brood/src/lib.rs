@@ -1,312 +1,6528 @@
 use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, Token, TokenAccount};
 
 declare_id!("2Au3HkZn7qQn4FgCSiH9cJGzPHtzGSmmmjaQhDXF5ZNV");
 
 // Constants
 const MAX_NAME_LEN: usize = 32;
 const MAX_URI_LEN: usize = 128;
+const MAX_METADATA_URI_LEN: usize = 200;
 const MIN_SPAWN_SEED: u64 = 100_000_000;  // 0.1 SOL
 const MIN_OPERATING_RESERVE: u64 = 50_000_000;  // 0.05 SOL
+const UPKEEP_PER_SECOND: u64 = 100;  // ~0.0086 SOL per day
+const ROYALTY_BPS: u16 = 250;  // 2.5% of spawn seed to the grandparent
+const PROTOCOL_FEE_BPS: u16 = 100;  // 1% of service payments to the protocol
+const SEED_GROWTH_BPS: u16 = 1_000;  // min spawn seed grows 10% per generation
+const MAX_COHORT_SIZE: usize = 10;  // agents per cull call, bounded by account limits
+const MAX_ADOPTION_DEPTH: usize = 16;  // ancestors walked when checking for cycles
+const GENERATION_PENALTY_BASE: u32 = 100;  // fitness scaled by base / (base + generation)
+const EARNINGS_WEIGHT_BPS: u16 = 10_000;  // all of net profit counts towards fitness
+const SCORE_WEIGHT: u64 = 0;  // tournament record ignored by default
+const SPAWN_FEE: u64 = 0;  // flat protocol fee per spawn, paid by the parent
+const TREASURY_DECAY_BPS_PER_DAY: u16 = 0;  // demurrage on idle treasuries; off by default
+const INACTIVITY_TIMEOUT: i64 = 0;  // seconds untouched before mark_inactive; zero disables
+const INACTIVITY_BOUNTY: u64 = 1_000_000;  // 0.001 SOL to whoever prunes an abandoned agent
+const GRACE_PERIOD_SECONDS: i64 = 0;  // how long an empty treasury survives; zero kills at once
+const MAX_PRICE_AGE: i64 = 60;  // seconds before a USD price feed counts as stale
+const MAX_GENERATION: u32 = 100;  // default lineage depth cap
+const MAX_CHILDREN: usize = 32;  // children tracked per Lineage account
+const MAX_ALLOWED_PAYERS: usize = 16;  // payers per PaymentPolicy allowlist
+const MAX_DISTRIBUTION_SHARES: usize = 16;  // recipients per distribute call
+const TREASURY_HISTORY_LEN: usize = 32;  // samples kept per TreasuryHistory
+const REVIVE_MIN_FUNDING: u64 = 100_000_000;  // 0.1 SOL
+const MIN_MATURITY_SECONDS: i64 = 0;  // default age before an agent may reproduce
+const MIN_SPAWN_SERVICES: u32 = 0;  // default services served before reproducing
+const WITHDRAW_WINDOW: i64 = 86_400;  // withdrawal cap resets daily
+const WITHDRAW_LIMIT_DELAY: i64 = 86_400;  // raising the cap waits a day
+const MAX_AGENTS_PER_OWNER: u32 = 0;  // default per-owner cap; zero means unlimited
+const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey = pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
 
 #[program]
 pub mod brood {
     use super::*;
 
+    /// Create the protocol-wide config. The first caller becomes its admin;
+    /// any parameter left unset falls back to the compiled-in default.
+    pub fn initialize_config(ctx: Context<InitializeConfig>, params: ConfigParams) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.protocol_treasury = ctx.accounts.admin.key();
+        config.protocol_fee_bps = PROTOCOL_FEE_BPS;
+        config.royalty_bps = ROYALTY_BPS;
+        config.min_operating_reserve = MIN_OPERATING_RESERVE;
+        config.min_spawn_seed = MIN_SPAWN_SEED;
+        config.max_generation = MAX_GENERATION;
+        config.seed_growth_bps = SEED_GROWTH_BPS;
+        config.min_maturity_seconds = MIN_MATURITY_SECONDS;
+        config.min_spawn_services = MIN_SPAWN_SERVICES;
+        config.max_agents_per_owner = MAX_AGENTS_PER_OWNER;
+        config.paused = false;
+        config.fitness_weights = FitnessWeights {
+            earnings_weight_bps: EARNINGS_WEIGHT_BPS,
+            score_weight: SCORE_WEIGHT,
+            generation_penalty: GENERATION_PENALTY_BASE,
+        };
+        config.spawn_fee = SPAWN_FEE;
+        config.decay_bps_per_day = TREASURY_DECAY_BPS_PER_DAY;
+        config.decay_rate_set_at = Clock::get()?.unix_timestamp;
+        config.inactivity_timeout = INACTIVITY_TIMEOUT;
+        config.inactivity_bounty = INACTIVITY_BOUNTY;
+        config.debug_compute = false;
+        config.grace_period_seconds = GRACE_PERIOD_SECONDS;
+        config.apply(params)?;
+
+        msg!("Protocol config initialized ({} bps fee)", config.protocol_fee_bps);
+        Ok(())
+    }
+
+    /// Halt every mutating instruction except `withdraw_treasury`, `retire`,
+    /// `cancel_subscription` and an owner's own `pause`, so users can still
+    /// get their funds out or lock an agent down while a bug is fixed.
+    pub fn emergency_pause(ctx: Context<EmergencyPause>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.paused = true;
+
+        emit!(EmergencyPaused {
+            admin: config.admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Program paused");
+        Ok(())
+    }
+
+    /// Lift an emergency pause
+    pub fn emergency_unpause(ctx: Context<EmergencyPause>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.paused = false;
+
+        emit!(EmergencyUnpaused {
+            admin: config.admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Program unpaused");
+        Ok(())
+    }
+
+    /// Change protocol parameters; unset fields are left as they are
+    pub fn update_config(ctx: Context<UpdateConfig>, params: ConfigParams) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.apply(params)?;
+
+        msg!("Protocol config updated");
+        Ok(())
+    }
+
     /// Create a new agent with genome stored off-chain
     pub fn create_agent(
         ctx: Context<CreateAgent>,
         name: String,
         genome_hash: [u8; 32],  // SHA256 of genome file
         genome_uri: String,     // IPFS/Arweave URI
+        global_name: bool,      // Also reserve the name across all owners
+        options: AgentOptions,
     ) -> Result<()> {
-        require!(name.len() <= MAX_NAME_LEN, BroodError::NameTooLong);
+        validate_name(&name)?;
         require!(genome_uri.len() <= MAX_URI_LEN, BroodError::UriTooLong);
+        require!(
+            global_name == ctx.accounts.name_registry.is_some(),
+            BroodError::NameRegistryMismatch
+        );
 
         let agent_key = ctx.accounts.agent.key();
         let agent = &mut ctx.accounts.agent;
         let clock = Clock::get()?;
 
-        agent.id = agent_key;
-        agent.owner = ctx.accounts.owner.key();
-        agent.parent = None;
-        agent.generation = 1;
-        agent.name = name;
-        agent.genome_hash = genome_hash;
-        agent.genome_uri = genome_uri;
-        agent.treasury = 0;
-        agent.total_earnings = 0;
-        agent.total_costs = 0;
-        agent.spawn_count = 0;
-        agent.service_count = 0;
-        agent.created_at = clock.unix_timestamp;
-        agent.last_active = clock.unix_timestamp;
-        agent.is_alive = true;
+        agent.init(
+            agent_key,
+            ctx.accounts.owner.key(),
+            name,
+            genome_hash,
+            genome_uri,
+            clock.unix_timestamp,
+        );
+        agent.agent_bump = ctx.bumps.agent;
+        agent.treasury_bump =
+            Pubkey::find_program_address(&[b"treasury", agent_key.as_ref()], ctx.program_id).1;
+        agent.treasury_mint = ctx.accounts.treasury_mint.as_ref().map(|mint| mint.key());
+        agent.apply_options(options, ctx.accounts.config.max_generation)?;
+        count_birth(
+            &mut ctx.accounts.population,
+            &mut ctx.accounts.owner_stats,
+            ctx.accounts.owner.key(),
+            ctx.accounts.config.max_agents_per_owner,
+        )?;
+
+        if let Some(registry) = &mut ctx.accounts.name_registry {
+            require_keys_eq!(registry.agent, Pubkey::default(), BroodError::NameTaken);
+            registry.agent = agent_key;
+        }
+
+        emit!(AgentCreated {
+            agent: agent_key,
+            owner: agent.owner,
+            name: agent.name.clone(),
+            generation: agent.generation,
+            genome_hash: agent.genome_hash,
+            genome_uri: agent.genome_uri.clone(),
+            timestamp: clock.unix_timestamp,
+        });
 
         msg!("Agent created: {} (gen 1)", agent.name);
         Ok(())
     }
 
-    /// Fund agent treasury with SOL
-    pub fn fund_treasury(ctx: Context<FundTreasury>, amount: u64) -> Result<()> {
+    /// Admin-only: create a sanctioned founding agent at generation 0 for
+    /// `owner`. User-created roots stay at generation 1.
+    pub fn create_genesis_agent(
+        ctx: Context<CreateGenesisAgent>,
+        name: String,
+        genome_hash: [u8; 32],
+        genome_uri: String,
+    ) -> Result<()> {
+        validate_name(&name)?;
+        require!(genome_uri.len() <= MAX_URI_LEN, BroodError::UriTooLong);
+
+        let agent_key = ctx.accounts.agent.key();
         let agent = &mut ctx.accounts.agent;
-        
-        let ix = anchor_lang::solana_program::system_instruction::transfer(
-            &ctx.accounts.funder.key(),
-            &ctx.accounts.treasury.key(),
-            amount,
+        let clock = Clock::get()?;
+
+        agent.init(
+            agent_key,
+            ctx.accounts.owner.key(),
+            name,
+            genome_hash,
+            genome_uri,
+            clock.unix_timestamp,
+        );
+        agent.agent_bump = ctx.bumps.agent;
+        agent.treasury_bump =
+            Pubkey::find_program_address(&[b"treasury", agent_key.as_ref()], ctx.program_id).1;
+        agent.max_generation = ctx.accounts.config.max_generation;
+        agent.generation = 0;
+        agent.birth_generation = 0;
+        agent.is_genesis = true;
+        count_birth(
+            &mut ctx.accounts.population,
+            &mut ctx.accounts.owner_stats,
+            ctx.accounts.owner.key(),
+            0,
+        )?;
+
+        emit!(AgentCreated {
+            agent: agent_key,
+            owner: agent.owner,
+            name: agent.name.clone(),
+            generation: agent.generation,
+            genome_hash: agent.genome_hash,
+            genome_uri: agent.genome_uri.clone(),
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Genesis agent created: {} (gen 0)", agent.name);
+        Ok(())
+    }
+
+    /// Create a native-SOL agent and fund it in one step, so it never
+    /// exists without an operating reserve
+    pub fn create_and_fund(
+        ctx: Context<CreateAndFund>,
+        name: String,
+        genome_hash: [u8; 32],
+        genome_uri: String,
+        options: AgentOptions,
+        funding: u64,
+    ) -> Result<()> {
+        validate_name(&name)?;
+        require!(genome_uri.len() <= MAX_URI_LEN, BroodError::UriTooLong);
+        require!(
+            funding >= ctx.accounts.config.min_operating_reserve,
+            BroodError::InsufficientTreasury
+        );
+
+        let agent_key = ctx.accounts.agent.key();
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        agent.init(
+            agent_key,
+            ctx.accounts.owner.key(),
+            name,
+            genome_hash,
+            genome_uri,
+            clock.unix_timestamp,
         );
-        anchor_lang::solana_program::program::invoke(
-            &ix,
-            &[
-                ctx.accounts.funder.to_account_info(),
-                ctx.accounts.treasury.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
+        agent.agent_bump = ctx.bumps.agent;
+        agent.treasury_bump = ctx.bumps.treasury;
+        agent.apply_options(options, ctx.accounts.config.max_generation)?;
+        count_birth(
+            &mut ctx.accounts.population,
+            &mut ctx.accounts.owner_stats,
+            ctx.accounts.owner.key(),
+            ctx.accounts.config.max_agents_per_owner,
+        )?;
+
+        deposit_lamports(
+            &ctx.accounts.owner,
+            &ctx.accounts.treasury,
+            &ctx.accounts.system_program,
+            funding,
         )?;
+        agent.treasury = funding;
+        assert_treasury_consistency(agent, &ctx.accounts.treasury)?;
+
+        emit!(AgentCreated {
+            agent: agent_key,
+            owner: agent.owner,
+            name: agent.name.clone(),
+            generation: agent.generation,
+            genome_hash: agent.genome_hash,
+            genome_uri: agent.genome_uri.clone(),
+            timestamp: clock.unix_timestamp,
+        });
+        emit!(TreasuryFunded {
+            agent: agent_key,
+            funder: agent.owner,
+            amount: funding,
+            treasury: agent.treasury,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent created: {} (gen 1), funded with {} lamports", agent.name, funding);
+        Ok(())
+    }
+
+    /// Fund agent treasury with SOL, or with tokens if it is bound to a mint
+    pub fn fund_treasury(ctx: Context<FundTreasury>, amount: u64) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        agent.require_fundable(amount)?;
+
+        match agent.treasury_mint {
+            None => {
+                deposit_lamports(
+                    &ctx.accounts.funder,
+                    &ctx.accounts.treasury,
+                    &ctx.accounts.system_program,
+                    amount,
+                )?;
+                agent.credit_treasury(amount)?;
+                assert_treasury_consistency(agent, &ctx.accounts.treasury)?;
+            }
+            Some(_) => {
+                let (Some(vault), Some(source), Some(token_program)) = (
+                    &mut ctx.accounts.treasury_token_account,
+                    &ctx.accounts.funder_token_account,
+                    &ctx.accounts.token_program,
+                ) else {
+                    return err!(BroodError::MissingTokenAccounts);
+                };
+                deposit_tokens(&ctx.accounts.funder, source, vault, token_program, amount)?;
+                agent.credit_treasury(amount)?;
+                assert_token_treasury_consistency(agent, vault)?;
+            }
+        }
+
+        end_grace(agent, clock.unix_timestamp);
+
+        if let Some(history) = &mut ctx.accounts.treasury_history {
+            history.record(agent.treasury, clock.unix_timestamp);
+        }
+
+        emit!(TreasuryFunded {
+            agent: agent.key(),
+            funder: ctx.accounts.funder.key(),
+            amount,
+            treasury: agent.treasury,
+            timestamp: clock.unix_timestamp,
+        });
+
+        if let Some(sponsorship) = &mut ctx.accounts.sponsorship {
+            sponsorship.agent = agent.key();
+            sponsorship.funder = ctx.accounts.funder.key();
+            sponsorship.total = sponsorship
+                .total
+                .checked_add(amount)
+                .ok_or(BroodError::MathOverflow)?;
+
+            emit!(Sponsored {
+                agent: agent.key(),
+                funder: sponsorship.funder,
+                amount,
+                total: sponsorship.total,
+                timestamp: clock.unix_timestamp,
+            });
+        }
 
-        agent.treasury += amount;
         msg!("Funded {} with {} lamports", agent.name, amount);
         Ok(())
     }
 
-    /// Spawn child agent with mutated genome
-    pub fn spawn(
-        ctx: Context<Spawn>,
-        child_name: String,
-        child_genome_hash: [u8; 32],  // Hash of mutated genome
-        child_genome_uri: String,      // URI of mutated genome
-        seed_amount: u64,
+    /// Set up, retune or top up a recurring subscription from the signer to
+    /// an agent. `deposit` is pre-funded into the subscription vault, from
+    /// which keepers pull `amount_per_interval` via `execute_subscription`.
+    pub fn subscribe(
+        ctx: Context<Subscribe>,
+        amount_per_interval: u64,
+        interval: i64,
+        deposit: u64,
     ) -> Result<()> {
-        require!(child_name.len() <= MAX_NAME_LEN, BroodError::NameTooLong);
-        require!(child_genome_uri.len() <= MAX_URI_LEN, BroodError::UriTooLong);
+        require!(amount_per_interval > 0, BroodError::ZeroAmount);
+        require!(interval > 0, BroodError::InvalidInterval);
+        require_native_treasury(&ctx.accounts.agent)?;
+        let balance = ctx
+            .accounts
+            .vault
+            .lamports()
+            .checked_add(deposit)
+            .ok_or(BroodError::MathOverflow)?;
+        require_treasury_rent(balance, true)?;
 
-        let child_key = ctx.accounts.child_agent.key();
-        let parent = &mut ctx.accounts.parent_agent;
-        let child = &mut ctx.accounts.child_agent;
+        let subscription = &mut ctx.accounts.subscription;
         let clock = Clock::get()?;
 
-        require!(parent.is_alive, BroodError::AgentDead);
-        require!(parent.treasury >= seed_amount + MIN_OPERATING_RESERVE, BroodError::InsufficientTreasury);
-        require!(seed_amount >= MIN_SPAWN_SEED, BroodError::InsufficientSpawnSeed);
+        subscription.agent = ctx.accounts.agent.key();
+        subscription.funder = ctx.accounts.funder.key();
+        subscription.amount_per_interval = amount_per_interval;
+        subscription.interval = interval;
 
-        // Initialize child with mutated genome
-        child.id = child_key;
-        child.owner = ctx.accounts.owner.key();
-        child.parent = Some(parent.id);
-        child.generation = parent.generation + 1;
-        child.name = child_name;
-        child.genome_hash = child_genome_hash;
-        child.genome_uri = child_genome_uri;
-        child.treasury = seed_amount;
-        child.total_earnings = 0;
-        child.total_costs = 0;
-        child.spawn_count = 0;
-        child.service_count = 0;
-        child.created_at = clock.unix_timestamp;
-        child.last_active = clock.unix_timestamp;
-        child.is_alive = true;
+        if deposit > 0 {
+            deposit_lamports(
+                &ctx.accounts.funder,
+                &ctx.accounts.vault,
+                &ctx.accounts.system_program,
+                deposit,
+            )?;
+        }
 
-        // Deduct from parent
-        parent.treasury -= seed_amount;
-        parent.spawn_count += 1;
+        emit!(Subscribed {
+            agent: subscription.agent,
+            funder: subscription.funder,
+            amount_per_interval,
+            interval,
+            balance: ctx.accounts.vault.lamports(),
+            timestamp: clock.unix_timestamp,
+        });
 
         msg!(
-            "Agent {} spawned {} (gen {})", 
-            parent.name, child.name, child.generation
+            "Subscribed {} lamports every {}s to {}",
+            amount_per_interval, interval, ctx.accounts.agent.name
         );
         Ok(())
     }
 
-    /// Record earnings from providing a service
-    pub fn record_earnings(ctx: Context<RecordEarnings>, amount: u64) -> Result<()> {
+    /// Move one interval's payment from a subscription vault into the agent's
+    /// treasury. Callable by anyone, at most once per interval; the last
+    /// payment may be smaller if the vault is running dry.
+    pub fn execute_subscription(ctx: Context<ExecuteSubscription>) -> Result<()> {
         let agent = &mut ctx.accounts.agent;
+        let subscription = &mut ctx.accounts.subscription;
         let clock = Clock::get()?;
 
         require!(agent.is_alive, BroodError::AgentDead);
+        require_native_treasury(agent)?;
+        let next_due = subscription.last_executed_at.saturating_add(subscription.interval);
+        require!(
+            subscription.last_executed_at == 0 || clock.unix_timestamp >= next_due,
+            BroodError::SubscriptionNotDue
+        );
 
-        agent.treasury += amount;
-        agent.total_earnings += amount;
-        agent.service_count += 1;
-        agent.last_active = clock.unix_timestamp;
+        let balance = ctx.accounts.vault.lamports();
+        require!(balance > 0, BroodError::SubscriptionEmpty);
+        let amount = sweep_below_rent(balance, subscription.amount_per_interval.min(balance))?;
+        agent.require_under_cap(amount)?;
+
+        transfer_from_agent_pda(
+            b"subscription_vault",
+            &subscription.key(),
+            ctx.bumps.vault,
+            &ctx.accounts.vault,
+            &ctx.accounts.treasury,
+            &ctx.accounts.system_program,
+            amount,
+        )?;
+        agent.treasury = agent
+            .treasury
+            .checked_add(amount)
+            .ok_or(BroodError::TreasuryOverflow)?;
+        assert_treasury_consistency(agent, &ctx.accounts.treasury)?;
+        end_grace(agent, clock.unix_timestamp);
+        subscription.last_executed_at = clock.unix_timestamp;
+
+        emit!(SubscriptionExecuted {
+            agent: agent.key(),
+            funder: subscription.funder,
+            amount,
+            treasury: agent.treasury,
+            timestamp: clock.unix_timestamp,
+        });
 
-        msg!("{} earned {} lamports", agent.name, amount);
+        msg!("Subscription paid {} lamports into {}", amount, agent.name);
         Ok(())
     }
 
-    /// Deduct operating costs
-    pub fn deduct_costs(ctx: Context<DeductCosts>, amount: u64) -> Result<()> {
+    /// End a subscription, refunding whatever is left in its vault to the funder
+    pub fn cancel_subscription(ctx: Context<CancelSubscription>) -> Result<()> {
+        let subscription = &ctx.accounts.subscription;
+        let clock = Clock::get()?;
+
+        let refunded = ctx.accounts.vault.lamports();
+        if refunded > 0 {
+            transfer_from_agent_pda(
+                b"subscription_vault",
+                &subscription.key(),
+                ctx.bumps.vault,
+                &ctx.accounts.vault,
+                &ctx.accounts.funder.to_account_info(),
+                &ctx.accounts.system_program,
+                refunded,
+            )?;
+        }
+
+        emit!(SubscriptionCancelled {
+            agent: subscription.agent,
+            funder: subscription.funder,
+            refunded,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Subscription to {} cancelled, {} lamports refunded", subscription.agent, refunded);
+        Ok(())
+    }
+
+    /// Withdraw SOL from agent treasury back to owner
+    pub fn withdraw_treasury(ctx: Context<WithdrawTreasury>, amount: u64) -> Result<()> {
         let agent = &mut ctx.accounts.agent;
         let clock = Clock::get()?;
 
-        require!(agent.is_alive, BroodError::AgentDead);
+        require!(!agent.frozen, BroodError::AgentFrozen);
         require!(agent.treasury >= amount, BroodError::InsufficientTreasury);
+        if agent.is_alive {
+            require!(
+                agent.treasury - amount >= ctx.accounts.config.min_operating_reserve,
+                BroodError::WithdrawalBelowReserve
+            );
+        }
+        agent.record_withdrawal(amount, clock.unix_timestamp)?;
 
-        agent.treasury -= amount;
-        agent.total_costs += amount;
-        agent.last_active = clock.unix_timestamp;
-
-        // Check for death condition
-        if agent.treasury == 0 {
-            agent.is_alive = false;
-            msg!("Agent {} has died (treasury depleted)", agent.name);
+        match agent.treasury_mint {
+            None => {
+                require_treasury_rent(agent.treasury - amount, !agent.is_alive)?;
+                transfer_from_treasury(
+                    &agent.key(),
+                    agent.treasury_bump,
+                    &ctx.accounts.treasury,
+                    &ctx.accounts.owner.to_account_info(),
+                    &ctx.accounts.system_program,
+                    amount,
+                )?;
+                agent.treasury = agent
+                    .treasury
+                    .checked_sub(amount)
+                    .ok_or(BroodError::MathOverflow)?;
+                assert_treasury_consistency(agent, &ctx.accounts.treasury)?;
+            }
+            Some(_) => {
+                let (Some(vault), Some(destination), Some(token_program)) = (
+                    &mut ctx.accounts.treasury_token_account,
+                    &ctx.accounts.owner_token_account,
+                    &ctx.accounts.token_program,
+                ) else {
+                    return err!(BroodError::MissingTokenAccounts);
+                };
+                transfer_tokens_from_treasury(
+                    &agent.key(),
+                    agent.treasury_bump,
+                    &ctx.accounts.treasury,
+                    vault,
+                    destination,
+                    token_program,
+                    amount,
+                )?;
+                agent.treasury = agent
+                    .treasury
+                    .checked_sub(amount)
+                    .ok_or(BroodError::MathOverflow)?;
+                assert_token_treasury_consistency(agent, vault)?;
+            }
         }
 
+        emit!(TreasuryWithdrawn {
+            agent: agent.key(),
+            owner: agent.owner,
+            amount,
+            treasury: agent.treasury,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Withdrew {} lamports from {}", amount, agent.name);
         Ok(())
     }
 
-    /// Update genome (only owner can do this)
-    pub fn update_genome(
-        ctx: Context<UpdateGenome>,
-        new_genome_hash: [u8; 32],
-        new_genome_uri: String,
+    /// Withdraw `amount` and split it between recipients by basis points,
+    /// which must sum to 10_000. Recipient accounts follow in remaining
+    /// accounts in the same order; rounding dust goes to the last one.
+    /// The same reserve and daily limit as `withdraw_treasury` apply.
+    pub fn distribute<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Distribute<'info>>,
+        amount: u64,
+        shares: Vec<Share>,
     ) -> Result<()> {
-        require!(new_genome_uri.len() <= MAX_URI_LEN, BroodError::UriTooLong);
+        require!(
+            !shares.is_empty()
+                && shares.len() <= MAX_DISTRIBUTION_SHARES
+                && shares.len() == ctx.remaining_accounts.len(),
+            BroodError::InvalidDistribution
+        );
+        let total_bps: u32 = shares.iter().map(|share| share.bps as u32).sum();
+        require!(total_bps == 10_000, BroodError::InvalidDistribution);
 
         let agent = &mut ctx.accounts.agent;
-        require!(agent.is_alive, BroodError::AgentDead);
+        let clock = Clock::get()?;
 
-        agent.genome_hash = new_genome_hash;
-        agent.genome_uri = new_genome_uri;
+        require_native_treasury(agent)?;
+        require!(!agent.frozen, BroodError::AgentFrozen);
+        require!(agent.treasury >= amount, BroodError::InsufficientTreasury);
+        if agent.is_alive {
+            require!(
+                agent.treasury - amount >= ctx.accounts.config.min_operating_reserve,
+                BroodError::WithdrawalBelowReserve
+            );
+        }
+        require_treasury_rent(agent.treasury - amount, !agent.is_alive)?;
+        agent.record_withdrawal(amount, clock.unix_timestamp)?;
 
-        msg!("Agent {} genome updated", agent.name);
+        let mut remaining = amount;
+        for (i, (share, recipient)) in shares.iter().zip(ctx.remaining_accounts).enumerate() {
+            require_keys_eq!(recipient.key(), share.recipient, BroodError::InvalidDistribution);
+            let portion = if i + 1 == shares.len() {
+                remaining
+            } else {
+                (amount as u128 * share.bps as u128 / 10_000) as u64
+            };
+            if portion > 0 {
+                transfer_from_treasury(
+                    &agent.key(),
+                    agent.treasury_bump,
+                    &ctx.accounts.treasury,
+                    recipient,
+                    &ctx.accounts.system_program,
+                    portion,
+                )?;
+            }
+            remaining -= portion;
+        }
+        agent.treasury = agent
+            .treasury
+            .checked_sub(amount)
+            .ok_or(BroodError::MathOverflow)?;
+        assert_treasury_consistency(agent, &ctx.accounts.treasury)?;
+
+        emit!(Distributed {
+            agent: agent.key(),
+            amount,
+            shares: shares.clone(),
+            treasury: agent.treasury,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Distributed {} lamports from {} to {} recipients", amount, agent.name, shares.len());
         Ok(())
     }
 
-    /// Kill an agent (only owner can do this)
-    pub fn kill_agent(ctx: Context<KillAgent>) -> Result<()> {
+    /// Move treasury lamports into the agent's stake vault. A yield source
+    /// pays rewards by depositing into the vault; the operating reserve
+    /// always stays behind in the treasury.
+    pub fn stake_treasury(ctx: Context<StakeTreasury>, amount: u64) -> Result<()> {
         let agent = &mut ctx.accounts.agent;
-        agent.is_alive = false;
-        msg!("Agent {} killed by owner", agent.name);
-        Ok(())
-    }
-}
+        let clock = Clock::get()?;
 
-// ============================================================================
-// ACCOUNTS
-// ============================================================================
+        require!(agent.is_alive, BroodError::AgentDead);
+        require_native_treasury(agent)?;
+        require!(agent.treasury >= amount, BroodError::InsufficientTreasury);
+        require!(
+            agent.treasury - amount >= ctx.accounts.config.min_operating_reserve,
+            BroodError::WithdrawalBelowReserve
+        );
+        require_treasury_rent(agent.treasury - amount, false)?;
 
-#[derive(Accounts)]
-#[instruction(name: String)]
-pub struct CreateAgent<'info> {
-    #[account(
-        init,
-        payer = owner,
-        space = 8 + Agent::INIT_SPACE,
-        seeds = [b"agent", owner.key().as_ref(), name.as_bytes()],
-        bump
-    )]
-    pub agent: Account<'info, Agent>,
+        transfer_from_treasury(
+            &agent.key(),
+            agent.treasury_bump,
+            &ctx.accounts.treasury,
+            &ctx.accounts.stake_vault,
+            &ctx.accounts.system_program,
+            amount,
+        )?;
 
-    #[account(mut)]
-    pub owner: Signer<'info>,
+        agent.treasury -= amount;
+        agent.staked_amount = agent
+            .staked_amount
+            .checked_add(amount)
+            .ok_or(BroodError::MathOverflow)?;
+        assert_treasury_consistency(agent, &ctx.accounts.treasury)?;
 
-    pub system_program: Program<'info, System>,
-}
+        emit!(TreasuryStaked {
+            agent: agent.key(),
+            amount,
+            staked_amount: agent.staked_amount,
+            treasury: agent.treasury,
+            timestamp: clock.unix_timestamp,
+        });
 
-#[derive(Accounts)]
-pub struct FundTreasury<'info> {
-    #[account(mut)]
-    pub agent: Account<'info, Agent>,
+        msg!("Staked {} lamports from {}", amount, agent.name);
+        Ok(())
+    }
 
-    /// CHECK: Treasury PDA, validated by seeds
-    #[account(
-        mut,
-        seeds = [b"treasury", agent.key().as_ref()],
-        bump
-    )]
-    pub treasury: AccountInfo<'info>,
+    /// Pull everything in the stake vault back into the treasury. Whatever
+    /// exceeds the staked principal counts as earnings.
+    pub fn unstake_treasury(ctx: Context<UnstakeTreasury>) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
 
-    #[account(mut)]
-    pub funder: Signer<'info>,
+        let returned = ctx.accounts.stake_vault.lamports();
+        let rewards = returned.saturating_sub(agent.staked_amount);
 
-    pub system_program: Program<'info, System>,
-}
+        if returned > 0 {
+            transfer_from_agent_pda(
+                b"stake",
+                &agent.key(),
+                ctx.bumps.stake_vault,
+                &ctx.accounts.stake_vault,
+                &ctx.accounts.treasury,
+                &ctx.accounts.system_program,
+                returned,
+            )?;
+        }
 
-#[derive(Accounts)]
-#[instruction(child_name: String)]
-pub struct Spawn<'info> {
-    #[account(
-        mut,
-        has_one = owner
-    )]
-    pub parent_agent: Account<'info, Agent>,
+        agent.treasury = agent
+            .treasury
+            .checked_add(returned)
+            .ok_or(BroodError::MathOverflow)?;
+        agent.total_earnings = agent
+            .total_earnings
+            .checked_add(rewards)
+            .ok_or(BroodError::MathOverflow)?;
+        agent.staked_amount = 0;
+        assert_treasury_consistency(agent, &ctx.accounts.treasury)?;
+        end_grace(agent, clock.unix_timestamp);
 
-    #[account(
-        init,
-        payer = owner,
-        space = 8 + Agent::INIT_SPACE,
-        seeds = [b"agent", owner.key().as_ref(), child_name.as_bytes()],
-        bump
-    )]
-    pub child_agent: Account<'info, Agent>,
+        emit!(TreasuryUnstaked {
+            agent: agent.key(),
+            returned,
+            rewards,
+            treasury: agent.treasury,
+            timestamp: clock.unix_timestamp,
+        });
 
-    #[account(mut)]
-    pub owner: Signer<'info>,
+        msg!("Unstaked {} lamports ({} rewards) into {}", returned, rewards, agent.name);
+        Ok(())
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    /// Spawn child agent with mutated genome. Passing a `child_owner`
+    /// signer spawns it for that wallet while the owner pays; it must sign
+    /// because the child counts against its per-owner agent limit.
+    pub fn spawn(
+        ctx: Context<Spawn>,
+        child_name: String,
+        child_genome_hash: [u8; 32],  // Hash of mutated genome
+        child_genome_uri: String,      // URI of mutated genome
+        seed_amount: u64,
+    ) -> Result<()> {
+        log_compute(&ctx.accounts.config, "spawn: entry");
+        validate_name(&child_name)?;
+        require!(child_genome_uri.len() <= MAX_URI_LEN, BroodError::UriTooLong);
 
-#[derive(Accounts)]
-pub struct RecordEarnings<'info> {
-    #[account(mut, has_one = owner)]
-    pub agent: Account<'info, Agent>,
-    pub owner: Signer<'info>,
-}
+        let child_key = ctx.accounts.child_agent.key();
+        let config = &ctx.accounts.config;
+        let parent = &mut ctx.accounts.parent_agent;
+        let child = &mut ctx.accounts.child_agent;
+        let clock = Clock::get()?;
 
-#[derive(Accounts)]
-pub struct DeductCosts<'info> {
-    #[account(mut, has_one = owner)]
-    pub agent: Account<'info, Agent>,
-    pub owner: Signer<'info>,
-}
+        require!(parent.is_alive, BroodError::AgentDead);
+        require!(!parent.is_paused, BroodError::AgentPaused);
+        require!(!parent.frozen, BroodError::AgentFrozen);
+        require_native_treasury(parent)?;
+        // The spawn fee comes out of the parent on top of the seed
+        let spawn_fee = config.spawn_fee;
+        let outlay = seed_amount.checked_add(spawn_fee).ok_or(BroodError::AmountOverflow)?;
+        require_spawn_funds(parent.treasury, outlay, config.min_operating_reserve)?;
+        require_treasury_rent(parent.treasury - outlay, false)?;
+        require!(
+            seed_amount >= config.min_seed_for_generation(parent.generation),
+            BroodError::InsufficientSpawnSeed
+        );
+        require!(parent.generation < parent.max_generation, BroodError::MaxGenerationExceeded);
+        require_spawn_cooldown_elapsed(parent, clock.unix_timestamp)?;
+        ctx.accounts.config.require_mature(parent, clock.unix_timestamp)?;
+        ctx.accounts.parent_lineage.record_child(child_key)?;
+        require_unused_name(child)?;
 
-#[derive(Accounts)]
-pub struct UpdateGenome<'info> {
-    #[account(mut, has_one = owner)]
-    pub agent: Account<'info, Agent>,
-    pub owner: Signer<'info>,
-}
+        // Initialize child with mutated genome
+        let child_owner = child_owner_key(&ctx.accounts.owner, &ctx.accounts.child_owner);
+        child.init(
+            child_key,
+            child_owner,
+            child_name,
+            child_genome_hash,
+            child_genome_uri,
+            clock.unix_timestamp,
+        );
+        child.agent_bump = ctx.bumps.child_agent;
+        child.treasury_bump = ctx.bumps.child_treasury;
+        child.parent = Some(parent.id);
+        child.generation = parent.generation.checked_add(1).ok_or(BroodError::MathOverflow)?;
+        child.birth_parent = child.parent;
+        child.birth_generation = child.generation;
+        child.root = parent.root;
+        ctx.accounts.brood.record_descendant(parent.root, child.generation)?;
+        count_birth(
+            &mut ctx.accounts.population,
+            &mut ctx.accounts.owner_stats,
+            child_owner,
+            config.max_agents_per_owner,
+        )?;
+        child.max_generation = parent.max_generation;
 
-#[derive(Accounts)]
-pub struct KillAgent<'info> {
-    #[account(mut, has_one = owner)]
-    pub agent: Account<'info, Agent>,
-    pub owner: Signer<'info>,
-}
+        // Route a royalty on the seed to a living grandparent
+        let mut royalty = 0;
+        if let Some(grandparent_key) = parent.parent {
+            let (Some(grandparent_info), Some(grandparent_treasury)) = (
+                &ctx.accounts.grandparent_agent,
+                &ctx.accounts.grandparent_treasury,
+            ) else {
+                return err!(BroodError::MissingGrandparent);
+            };
+            require_keys_eq!(grandparent_info.key(), grandparent_key, BroodError::GrandparentMismatch);
+            let (expected_treasury, _) = Pubkey::find_program_address(
+                &[b"treasury", grandparent_key.as_ref()],
+                ctx.program_id,
+            );
+            require_keys_eq!(grandparent_treasury.key(), expected_treasury, BroodError::GrandparentMismatch);
 
-// ============================================================================
-// STATE
-// ============================================================================
+            // A reaped grandparent has no account left to credit
+            if !grandparent_info.data_is_empty() {
+                let mut grandparent = load_agent(grandparent_info)?;
+                if grandparent.is_alive && grandparent.treasury_mint.is_none() {
+                    royalty = (seed_amount as u128 * config.royalty_bps as u128 / 10_000) as u64;
+                    transfer_from_treasury(
+                        &parent.key(),
+                        parent.treasury_bump,
+                        &ctx.accounts.parent_treasury,
+                        grandparent_treasury,
+                        &ctx.accounts.system_program,
+                        royalty,
+                    )?;
+                    grandparent.treasury = grandparent
+                        .treasury
+                        .checked_add(royalty)
+                        .ok_or(BroodError::MathOverflow)?;
+                    grandparent.royalties_earned = grandparent
+                        .royalties_earned
+                        .checked_add(royalty)
+                        .ok_or(BroodError::MathOverflow)?;
+                    assert_treasury_consistency(&mut grandparent, grandparent_treasury)?;
+                    end_grace(&mut grandparent, clock.unix_timestamp);
+                    store_agent(grandparent_info, &grandparent)?;
 
-#[account]
+                    emit!(RoyaltyPaid {
+                        parent: parent.key(),
+                        grandparent: grandparent_key,
+                        amount: royalty,
+                        royalties_earned: grandparent.royalties_earned,
+                        timestamp: clock.unix_timestamp,
+                    });
+                }
+            }
+        }
+        child.treasury = seed_amount - royalty;
+
+        // Move the rest of the seed from parent treasury to child treasury
+        transfer_from_treasury(
+            &parent.key(),
+            parent.treasury_bump,
+            &ctx.accounts.parent_treasury,
+            &ctx.accounts.child_treasury,
+            &ctx.accounts.system_program,
+            child.treasury,
+        )?;
+
+        if spawn_fee > 0 {
+            transfer_from_treasury(
+                &parent.key(),
+                parent.treasury_bump,
+                &ctx.accounts.parent_treasury,
+                &ctx.accounts.protocol_treasury,
+                &ctx.accounts.system_program,
+                spawn_fee,
+            )?;
+        }
+
+        // Deduct from parent
+        parent.treasury = parent.treasury.checked_sub(outlay).ok_or(BroodError::MathOverflow)?;
+        parent.total_spawn_fees_paid = parent
+            .total_spawn_fees_paid
+            .checked_add(spawn_fee)
+            .ok_or(BroodError::MathOverflow)?;
+        parent.spawn_count = parent.spawn_count.checked_add(1).ok_or(BroodError::MathOverflow)?;
+        parent.last_spawn_at = clock.unix_timestamp;
+
+        assert_treasury_consistency(parent, &ctx.accounts.parent_treasury)?;
+        assert_treasury_consistency(child, &ctx.accounts.child_treasury)?;
+
+        emit!(AgentSpawned {
+            parent: parent.key(),
+            child: child_key,
+            owner: child.owner,
+            name: child.name.clone(),
+            generation: child.generation,
+            genome_hash: child.genome_hash,
+            genome_uri: child.genome_uri.clone(),
+            seed_amount,
+            parent_treasury: parent.treasury,
+            spawn_fee,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!(
+            "Agent {} spawned {} (gen {})", 
+            parent.name, child.name, child.generation
+        );
+        log_compute(&ctx.accounts.config, "spawn: exit");
+        Ok(())
+    }
+
+    /// Spawn with the seed given as a share of the parent's treasury,
+    /// in basis points. The same seed and reserve minimums apply.
+    pub fn spawn_pct(
+        ctx: Context<Spawn>,
+        child_name: String,
+        child_genome_hash: [u8; 32],
+        child_genome_uri: String,
+        seed_bps: u16,
+    ) -> Result<()> {
+        require!(seed_bps <= 10_000, BroodError::InvalidBps);
+        let treasury = ctx.accounts.parent_agent.treasury;
+        let seed_amount = (treasury as u128 * seed_bps as u128 / 10_000) as u64;
+        spawn(ctx, child_name, child_genome_hash, child_genome_uri, seed_amount)
+    }
+
+    /// Spawn a child carrying the parent's genome hash and URI unchanged,
+    /// for controlled experiments. Emits `Cloned` on top of `AgentSpawned`.
+    pub fn clone_agent(ctx: Context<Spawn>, child_name: String, seed_amount: u64) -> Result<()> {
+        let parent = ctx.accounts.parent_agent.key();
+        let child = ctx.accounts.child_agent.key();
+        let genome_hash = ctx.accounts.parent_agent.genome_hash;
+        let genome_uri = ctx.accounts.parent_agent.genome_uri.clone();
+
+        spawn(ctx, child_name, genome_hash, genome_uri, seed_amount)?;
+
+        emit!(Cloned {
+            parent,
+            child,
+            genome_hash,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Spawn a child but hold `escrow_amount` of the parent's treasury back
+    /// in an escrow vault. After `probation_seconds` it goes to the child if
+    /// it survived, otherwise back to the parent; see `release_escrow`.
+    pub fn spawn_with_escrow<'info>(
+        ctx: Context<'_, '_, '_, 'info, SpawnWithEscrow<'info>>,
+        child_name: String,
+        child_genome_hash: [u8; 32],
+        child_genome_uri: String,
+        seed_amount: u64,
+        escrow_amount: u64,
+        probation_seconds: i64,
+    ) -> Result<()> {
+        require!(probation_seconds >= 0, BroodError::InvalidProbation);
+        require!(escrow_amount >= min_treasury_rent()?, BroodError::WouldDeallocateTreasury);
+
+        let parent = &mut ctx.accounts.spawn.parent_agent;
+        require_native_treasury(parent)?;
+        require!(parent.treasury >= escrow_amount, BroodError::InsufficientTreasury);
+
+        // Lock the escrow first so spawn's reserve checks see what's left
+        transfer_from_treasury(
+            &parent.key(),
+            parent.treasury_bump,
+            &ctx.accounts.spawn.parent_treasury,
+            &ctx.accounts.escrow_vault,
+            &ctx.accounts.spawn.system_program,
+            escrow_amount,
+        )?;
+        parent.treasury -= escrow_amount;
+
+        let parent_key = parent.key();
+        let child_key = ctx.accounts.spawn.child_agent.key();
+        spawn(
+            Context::new(
+                ctx.program_id,
+                &mut ctx.accounts.spawn,
+                ctx.remaining_accounts,
+                ctx.bumps.spawn,
+            ),
+            child_name,
+            child_genome_hash,
+            child_genome_uri,
+            seed_amount,
+        )?;
+
+        let clock = Clock::get()?;
+        let child = &mut ctx.accounts.spawn.child_agent;
+        child.escrow_amount = escrow_amount;
+        child.probation_ends_at = clock.unix_timestamp.saturating_add(probation_seconds);
+
+        emit!(EscrowLocked {
+            parent: parent_key,
+            child: child_key,
+            amount: escrow_amount,
+            probation_ends_at: child.probation_ends_at,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Escrowed {} lamports for {}", escrow_amount, child.name);
+        Ok(())
+    }
+
+    /// Spawn with a head start: the child takes `inherit_score_bps` of the
+    /// parent's tournament record, wins and losses alike, so a losing
+    /// parent passes on its debt. With `floor_at_zero` the child never
+    /// starts with more losses than wins.
+    pub fn spawn_with_score<'info>(
+        ctx: Context<'_, '_, '_, 'info, Spawn<'info>>,
+        child_name: String,
+        child_genome_hash: [u8; 32],
+        child_genome_uri: String,
+        seed_amount: u64,
+        inherit_score_bps: u16,
+        floor_at_zero: bool,
+    ) -> Result<()> {
+        require!(inherit_score_bps <= 10_000, BroodError::InvalidBps);
+
+        let parent = &ctx.accounts.parent_agent;
+        let parent_key = parent.key();
+        let (wins, losses) = parent.inherited_record(inherit_score_bps, floor_at_zero);
+
+        let Context { program_id, accounts, remaining_accounts, bumps, .. } = ctx;
+        spawn(
+            Context::new(program_id, accounts, remaining_accounts, bumps),
+            child_name,
+            child_genome_hash,
+            child_genome_uri,
+            seed_amount,
+        )?;
+
+        let clock = Clock::get()?;
+        let child = &mut accounts.child_agent;
+        child.wins = wins;
+        child.losses = losses;
+
+        emit!(ScoreInherited {
+            parent: parent_key,
+            child: child.key(),
+            wins,
+            losses,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} inherits a {}-{} record", child.name, wins, losses);
+        Ok(())
+    }
+
+    /// Settle a child's escrow once probation is over: into the child's
+    /// treasury if it is alive, otherwise back to the birth parent that
+    /// funded it, even if the child was adopted since. A parent that
+    /// has since been reaped forfeits it to the child. Anyone may call this.
+    pub fn release_escrow(ctx: Context<ReleaseEscrow>) -> Result<()> {
+        let child = &mut ctx.accounts.child_agent;
+        let clock = Clock::get()?;
+
+        require!(child.escrow_amount > 0, BroodError::NoEscrow);
+        require!(
+            clock.unix_timestamp >= child.probation_ends_at,
+            BroodError::ProbationActive
+        );
+
+        let amount = ctx.accounts.escrow_vault.lamports();
+        let parent_info = ctx.accounts.parent_agent.to_account_info();
+        let parent_exists = *parent_info.owner == crate::ID && !parent_info.data_is_empty();
+
+        let to_child = child.is_alive || !parent_exists;
+        let recipient = if to_child {
+            transfer_from_agent_pda(
+                b"escrow",
+                &child.key(),
+                ctx.bumps.escrow_vault,
+                &ctx.accounts.escrow_vault,
+                &ctx.accounts.child_treasury,
+                &ctx.accounts.system_program,
+                amount,
+            )?;
+            child.treasury = child.treasury.checked_add(amount).ok_or(BroodError::MathOverflow)?;
+            assert_treasury_consistency(child, &ctx.accounts.child_treasury)?;
+            end_grace(child, clock.unix_timestamp);
+            child.key()
+        } else {
+            let mut parent = load_agent(&parent_info)?;
+            require_keys_eq!(
+                ctx.accounts.parent_treasury.key(),
+                treasury_address(parent_info.key, parent.treasury_bump, ctx.program_id)?,
+                BroodError::TreasuryMismatch
+            );
+            // A parent that switched to a token treasury can't take lamports back
+            require_native_treasury(&parent)?;
+            transfer_from_agent_pda(
+                b"escrow",
+                &child.key(),
+                ctx.bumps.escrow_vault,
+                &ctx.accounts.escrow_vault,
+                &ctx.accounts.parent_treasury,
+                &ctx.accounts.system_program,
+                amount,
+            )?;
+            parent.treasury = parent.treasury.checked_add(amount).ok_or(BroodError::MathOverflow)?;
+            assert_treasury_consistency(&mut parent, &ctx.accounts.parent_treasury)?;
+            end_grace(&mut parent, clock.unix_timestamp);
+            store_agent(&parent_info, &parent)?;
+            parent_info.key()
+        };
+
+        child.escrow_amount = 0;
+
+        emit!(EscrowReleased {
+            child: child.key(),
+            recipient,
+            amount,
+            to_child,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Escrow of {} released to {}", child.name, recipient);
+        Ok(())
+    }
+
+    /// Breed a child from two parents. The caller supplies the crossed-over
+    /// genome; the seed is split between both parents' treasuries.
+    pub fn breed(
+        ctx: Context<Breed>,
+        child_name: String,
+        child_genome_hash: [u8; 32],  // Hash of crossed-over genome
+        child_genome_uri: String,      // URI of crossed-over genome
+        seed_amount: u64,
+    ) -> Result<()> {
+        log_compute(&ctx.accounts.config, "breed: entry");
+        validate_name(&child_name)?;
+        require!(child_genome_uri.len() <= MAX_URI_LEN, BroodError::UriTooLong);
+        require_keys_neq!(
+            ctx.accounts.parent_a.key(),
+            ctx.accounts.parent_b.key(),
+            BroodError::DuplicateParent
+        );
+
+        let child_key = ctx.accounts.child_agent.key();
+        let config = &ctx.accounts.config;
+        let parent_a = &mut ctx.accounts.parent_a;
+        let parent_b = &mut ctx.accounts.parent_b;
+        let child = &mut ctx.accounts.child_agent;
+        let clock = Clock::get()?;
+
+        // Parent A covers the odd lamport
+        let share_b = seed_amount / 2;
+        let share_a = seed_amount - share_b;
+
+        require!(parent_a.is_alive && parent_b.is_alive, BroodError::AgentDead);
+        require!(!parent_a.is_paused && !parent_b.is_paused, BroodError::AgentPaused);
+        require!(!parent_a.frozen && !parent_b.frozen, BroodError::AgentFrozen);
+        require_native_treasury(parent_a)?;
+        require_native_treasury(parent_b)?;
+        require_spawn_funds(parent_a.treasury, share_a, config.min_operating_reserve)?;
+        require_spawn_funds(parent_b.treasury, share_b, config.min_operating_reserve)?;
+        require_treasury_rent(parent_a.treasury - share_a, false)?;
+        require_treasury_rent(parent_b.treasury - share_b, false)?;
+        let deepest = parent_a.generation.max(parent_b.generation);
+        require!(
+            seed_amount >= config.min_seed_for_generation(deepest),
+            BroodError::InsufficientSpawnSeed
+        );
+
+        // A cross-lineage child inherits the stricter of the two caps
+        let max_generation = parent_a.max_generation.min(parent_b.max_generation);
+        require!(deepest < max_generation, BroodError::MaxGenerationExceeded);
+        require_spawn_cooldown_elapsed(parent_a, clock.unix_timestamp)?;
+        require_spawn_cooldown_elapsed(parent_b, clock.unix_timestamp)?;
+        ctx.accounts.config.require_mature(parent_a, clock.unix_timestamp)?;
+        ctx.accounts.config.require_mature(parent_b, clock.unix_timestamp)?;
+        ctx.accounts.parent_a_lineage.record_child(child_key)?;
+        ctx.accounts.parent_b_lineage.record_child(child_key)?;
+        require_unused_name(child)?;
+
+        child.init(
+            child_key,
+            ctx.accounts.owner.key(),
+            child_name,
+            child_genome_hash,
+            child_genome_uri,
+            clock.unix_timestamp,
+        );
+        child.agent_bump = ctx.bumps.child_agent;
+        child.treasury_bump = ctx.bumps.child_treasury;
+        child.parent = Some(parent_a.id);
+        child.co_parent = Some(parent_b.id);
+        child.generation = parent_a
+            .generation
+            .max(parent_b.generation)
+            .checked_add(1)
+            .ok_or(BroodError::MathOverflow)?;
+        child.birth_parent = child.parent;
+        child.birth_generation = child.generation;
+        child.root = parent_a.root;
+        ctx.accounts.brood.record_descendant(parent_a.root, child.generation)?;
+        count_birth(
+            &mut ctx.accounts.population,
+            &mut ctx.accounts.owner_stats,
+            ctx.accounts.owner.key(),
+            config.max_agents_per_owner,
+        )?;
+        child.max_generation = max_generation;
+        child.treasury = seed_amount;
+
+        transfer_from_treasury(
+            &parent_a.key(),
+            parent_a.treasury_bump,
+            &ctx.accounts.parent_a_treasury,
+            &ctx.accounts.child_treasury,
+            &ctx.accounts.system_program,
+            share_a,
+        )?;
+        transfer_from_treasury(
+            &parent_b.key(),
+            parent_b.treasury_bump,
+            &ctx.accounts.parent_b_treasury,
+            &ctx.accounts.child_treasury,
+            &ctx.accounts.system_program,
+            share_b,
+        )?;
+
+        parent_a.treasury = parent_a.treasury.checked_sub(share_a).ok_or(BroodError::MathOverflow)?;
+        parent_a.spawn_count = parent_a.spawn_count.checked_add(1).ok_or(BroodError::MathOverflow)?;
+        parent_b.treasury = parent_b.treasury.checked_sub(share_b).ok_or(BroodError::MathOverflow)?;
+        parent_b.spawn_count = parent_b.spawn_count.checked_add(1).ok_or(BroodError::MathOverflow)?;
+        parent_a.last_spawn_at = clock.unix_timestamp;
+        parent_b.last_spawn_at = clock.unix_timestamp;
+
+        assert_treasury_consistency(parent_a, &ctx.accounts.parent_a_treasury)?;
+        assert_treasury_consistency(parent_b, &ctx.accounts.parent_b_treasury)?;
+        assert_treasury_consistency(child, &ctx.accounts.child_treasury)?;
+
+        emit!(AgentBred {
+            parent_a: parent_a.key(),
+            parent_b: parent_b.key(),
+            child: child_key,
+            owner: child.owner,
+            name: child.name.clone(),
+            generation: child.generation,
+            genome_hash: child.genome_hash,
+            genome_uri: child.genome_uri.clone(),
+            seed_amount,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!(
+            "Agents {} and {} bred {} (gen {})",
+            parent_a.name, parent_b.name, child.name, child.generation
+        );
+        log_compute(&ctx.accounts.config, "breed: exit");
+        Ok(())
+    }
+
+    /// Record earnings from providing a service, depositing them into the
+    /// treasury. Anyone can pay, including another program: CPI with the
+    /// accounts of `RecordEarnings` and sign for `payer` with
+    /// `invoke_signed` using the calling program's PDA seeds. For native
+    /// treasuries that PDA must be a system-owned account holding the lamports.
+    pub fn record_earnings(ctx: Context<RecordEarnings>, amount: u64) -> Result<()> {
+        log_compute(&ctx.accounts.config, "record_earnings: entry");
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        require_payable(
+            agent,
+            amount,
+            &ctx.accounts.payer.key(),
+            ctx.accounts.payment_policy.as_deref(),
+            ctx.accounts.price_feed.as_deref(),
+            clock.unix_timestamp,
+        )?;
+
+        let treasury_before = agent.treasury;
+
+        // The protocol takes its cut first; only the net reaches the agent
+        let (fee, net) = ctx.accounts.config.split_service_fee(amount)?;
+        agent.require_under_cap(net)?;
+
+        match agent.treasury_mint {
+            None => {
+                if fee > 0 {
+                    deposit_lamports(
+                        &ctx.accounts.payer,
+                        &ctx.accounts.protocol_treasury,
+                        &ctx.accounts.system_program,
+                        fee,
+                    )?;
+                }
+                deposit_lamports(
+                    &ctx.accounts.payer,
+                    &ctx.accounts.treasury,
+                    &ctx.accounts.system_program,
+                    net,
+                )?;
+                agent.treasury = agent
+                    .treasury
+                    .checked_add(net)
+                    .ok_or(BroodError::MathOverflow)?;
+                assert_treasury_consistency(agent, &ctx.accounts.treasury)?;
+            }
+            Some(_) => {
+                let (Some(vault), Some(source), Some(token_program), Some(protocol_vault)) = (
+                    &mut ctx.accounts.treasury_token_account,
+                    &ctx.accounts.payer_token_account,
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.protocol_token_account,
+                ) else {
+                    return err!(BroodError::MissingTokenAccounts);
+                };
+                if fee > 0 {
+                    deposit_tokens(
+                        &ctx.accounts.payer,
+                        source,
+                        protocol_vault,
+                        token_program,
+                        fee,
+                    )?;
+                }
+                deposit_tokens(&ctx.accounts.payer, source, vault, token_program, net)?;
+                agent.treasury = agent
+                    .treasury
+                    .checked_add(net)
+                    .ok_or(BroodError::MathOverflow)?;
+                assert_token_treasury_consistency(agent, vault)?;
+            }
+        }
+        end_grace(agent, clock.unix_timestamp);
+
+        agent.record_service(net, &ctx.accounts.config.fitness_weights, clock.unix_timestamp)?;
+
+        // A fresh marker means this payer has never paid this agent before
+        if let Some(marker) = &mut ctx.accounts.payer_marker {
+            if marker.agent == Pubkey::default() {
+                marker.agent = agent.key();
+                marker.payer = ctx.accounts.payer.key();
+                agent.unique_payers = agent
+                    .unique_payers
+                    .checked_add(1)
+                    .ok_or(BroodError::MathOverflow)?;
+            }
+        }
+
+        if let Some(history) = &mut ctx.accounts.treasury_history {
+            history.record(agent.treasury, clock.unix_timestamp);
+        }
+
+        emit!(ServicePaid {
+            agent: agent.key(),
+            payer: ctx.accounts.payer.key(),
+            amount,
+            treasury: agent.treasury,
+            total_earnings: agent.total_earnings,
+            service_count: agent.service_count,
+            timestamp: clock.unix_timestamp,
+            protocol_fee: fee,
+            service_price: agent.service_price,
+        });
+
+        emit_if_spawn_eligible(agent, treasury_before, clock.unix_timestamp);
+
+        msg!("{} earned {} lamports ({} protocol fee)", agent.name, net, fee);
+        log_compute(&ctx.accounts.config, "record_earnings: exit");
+        Ok(())
+    }
+
+    /// One agent buys a service from another, paid out of the buyer's
+    /// treasury. The buyer's owner or operator signs; the seller's price,
+    /// allowlist and rate limit apply as for any other payer.
+    pub fn pay_for_service_from_agent(
+        ctx: Context<PayForServiceFromAgent>,
+        amount: u64,
+    ) -> Result<()> {
+        log_compute(&ctx.accounts.config, "pay_for_service_from_agent: entry");
+        require_keys_neq!(
+            ctx.accounts.buyer.key(),
+            ctx.accounts.seller.key(),
+            BroodError::SelfPayment
+        );
+        let clock = Clock::get()?;
+        let buyer = &mut ctx.accounts.buyer;
+        let seller = &mut ctx.accounts.seller;
+
+        require!(buyer.is_alive, BroodError::AgentDead);
+        require!(!buyer.is_paused, BroodError::AgentPaused);
+        require!(!buyer.frozen, BroodError::AgentFrozen);
+        require_native_treasury(buyer)?;
+        require_native_treasury(seller)?;
+        require!(buyer.treasury >= amount, BroodError::InsufficientTreasury);
+        require!(
+            buyer.treasury - amount >= ctx.accounts.config.min_operating_reserve,
+            BroodError::WithdrawalBelowReserve
+        );
+        require_treasury_rent(buyer.treasury - amount, false)?;
+        require_payable(
+            seller,
+            amount,
+            &buyer.key(),
+            ctx.accounts.payment_policy.as_deref(),
+            ctx.accounts.price_feed.as_deref(),
+            clock.unix_timestamp,
+        )?;
+
+        let seller_treasury_before = seller.treasury;
+        let (fee, net) = ctx.accounts.config.split_service_fee(amount)?;
+        seller.require_under_cap(net)?;
+
+        if fee > 0 {
+            transfer_from_treasury(
+                &buyer.key(),
+                buyer.treasury_bump,
+                &ctx.accounts.buyer_treasury,
+                &ctx.accounts.protocol_treasury,
+                &ctx.accounts.system_program,
+                fee,
+            )?;
+        }
+        transfer_from_treasury(
+            &buyer.key(),
+            buyer.treasury_bump,
+            &ctx.accounts.buyer_treasury,
+            &ctx.accounts.seller_treasury,
+            &ctx.accounts.system_program,
+            net,
+        )?;
+
+        buyer.treasury -= amount;
+        buyer.total_costs = buyer.total_costs.checked_add(amount).ok_or(BroodError::MathOverflow)?;
+        buyer.last_active = clock.unix_timestamp;
+        seller.treasury = seller.treasury.checked_add(net).ok_or(BroodError::MathOverflow)?;
+        seller.record_service(net, &ctx.accounts.config.fitness_weights, clock.unix_timestamp)?;
+        assert_treasury_consistency(buyer, &ctx.accounts.buyer_treasury)?;
+        assert_treasury_consistency(seller, &ctx.accounts.seller_treasury)?;
+        end_grace(seller, clock.unix_timestamp);
+
+        emit!(AgentToAgentService {
+            buyer: buyer.key(),
+            seller: seller.key(),
+            amount,
+            protocol_fee: fee,
+            buyer_treasury: buyer.treasury,
+            seller_treasury: seller.treasury,
+            timestamp: clock.unix_timestamp,
+        });
+
+        emit_if_spawn_eligible(seller, seller_treasury_before, clock.unix_timestamp);
+
+        msg!("{} paid {} {} lamports ({} protocol fee)", buyer.name, seller.name, net, fee);
+        log_compute(&ctx.accounts.config, "pay_for_service_from_agent: exit");
+        Ok(())
+    }
+
+    /// Deduct discretionary operating costs, paying them out of the treasury
+    /// to the cost sink. Owner or operator only; routine upkeep goes through
+    /// `accrue_costs`. Emptying the treasury starts the grace period; a
+    /// later deduction once it has run out kills the agent.
+    pub fn deduct_costs(ctx: Context<DeductCosts>, amount: u64) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        require!(agent.is_alive, BroodError::AgentDead);
+        require_native_treasury(agent)?;
+        require!(agent.treasury >= amount, BroodError::InsufficientTreasury);
+        require_treasury_rent(agent.treasury - amount, true)?;
+
+        transfer_from_treasury(
+            &agent.key(),
+            agent.treasury_bump,
+            &ctx.accounts.treasury,
+            &ctx.accounts.cost_sink,
+            &ctx.accounts.system_program,
+            amount,
+        )?;
+
+        agent.treasury = agent.treasury.checked_sub(amount).ok_or(BroodError::MathOverflow)?;
+        agent.total_costs = agent.total_costs.checked_add(amount).ok_or(BroodError::MathOverflow)?;
+        agent.last_active = clock.unix_timestamp;
+        assert_treasury_consistency(agent, &ctx.accounts.treasury)?;
+
+        if let Some(history) = &mut ctx.accounts.treasury_history {
+            history.record(agent.treasury, clock.unix_timestamp);
+        }
+
+        emit!(CostsDeducted {
+            agent: agent.key(),
+            amount,
+            cost_sink: ctx.accounts.cost_sink.key(),
+            treasury: agent.treasury,
+            total_costs: agent.total_costs,
+            timestamp: clock.unix_timestamp,
+        });
+
+        // Check for death condition
+        if agent.treasury == 0 {
+            if settle_depletion(
+                agent,
+                DeathReason::TreasuryDepleted,
+                ctx.accounts.config.grace_period_seconds,
+                amount > 0,
+                clock.unix_timestamp,
+            ) {
+                msg!("Agent {} has died (treasury depleted)", agent.name);
+            }
+        } else if agent.below_performance_floor() {
+            mark_dead(agent, DeathReason::PerformanceFloor, clock.unix_timestamp);
+            msg!("Agent {} has died (below performance floor)", agent.name);
+        }
+
+        Ok(())
+    }
+
+    /// Charge upkeep for the time elapsed since upkeep was last charged,
+    /// paid to a sink of the authority's choosing. An agent that can't
+    /// cover it pays what it has and dies once the grace period runs out.
+    pub fn tick(ctx: Context<Tick>) -> Result<()> {
+        let clock = Clock::get()?;
+        charge_upkeep(
+            &mut ctx.accounts.agent,
+            &ctx.accounts.treasury,
+            &ctx.accounts.cost_sink,
+            &ctx.accounts.system_program,
+            ctx.accounts.config.grace_period_seconds,
+            clock.unix_timestamp,
+        )
+    }
+
+    /// Prove the agent is still tended, resetting its inactivity clock
+    pub fn heartbeat(ctx: Context<Heartbeat>) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        require!(agent.is_alive, BroodError::AgentDead);
+        agent.last_active = clock.unix_timestamp;
+
+        emit!(HeartbeatRecorded {
+            agent: agent.key(),
+            timestamp: clock.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Kill an agent nobody has touched for `inactivity_timeout` seconds.
+    /// Anyone may call this; the caller is paid up to `inactivity_bounty`
+    /// from a native treasury for the trouble.
+    pub fn mark_inactive(ctx: Context<MarkInactive>) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let config = &ctx.accounts.config;
+        let clock = Clock::get()?;
+
+        require!(agent.is_alive, BroodError::AgentDead);
+        let idle = clock.unix_timestamp.saturating_sub(agent.last_active);
+        require!(
+            config.inactivity_timeout > 0 && idle > config.inactivity_timeout,
+            BroodError::AgentStillActive
+        );
+
+        let mut bounty = 0;
+        if agent.treasury_mint.is_none() {
+            let owed = config.inactivity_bounty.min(agent.treasury);
+            bounty = sweep_below_rent(agent.treasury, owed)?;
+            if bounty > 0 {
+                transfer_from_treasury(
+                    &agent.key(),
+                    agent.treasury_bump,
+                    &ctx.accounts.treasury,
+                    &ctx.accounts.caller.to_account_info(),
+                    &ctx.accounts.system_program,
+                    bounty,
+                )?;
+                agent.treasury -= bounty;
+                agent.total_costs = agent
+                    .total_costs
+                    .checked_add(bounty)
+                    .ok_or(BroodError::MathOverflow)?;
+                assert_treasury_consistency(agent, &ctx.accounts.treasury)?;
+            }
+        }
+
+        mark_dead(agent, DeathReason::Inactive, clock.unix_timestamp);
+
+        emit!(MarkedInactive {
+            agent: agent.key(),
+            caller: ctx.accounts.caller.key(),
+            bounty,
+            idle_seconds: idle,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} marked inactive after {}s", agent.name, idle);
+        Ok(())
+    }
+
+    /// Charge accrued treasury decay and upkeep to the protocol treasury.
+    /// Both follow from elapsed time alone, so anyone may call this.
+    pub fn accrue_costs(ctx: Context<AccrueCosts>) -> Result<()> {
+        let clock = Clock::get()?;
+        charge_decay(
+            &mut ctx.accounts.agent,
+            &ctx.accounts.treasury,
+            &ctx.accounts.protocol_treasury,
+            &ctx.accounts.system_program,
+            ctx.accounts.config.decay_bps_per_day,
+            ctx.accounts.config.decay_rate_set_at,
+            clock.unix_timestamp,
+        )?;
+        charge_upkeep(
+            &mut ctx.accounts.agent,
+            &ctx.accounts.treasury,
+            &ctx.accounts.protocol_treasury,
+            &ctx.accounts.system_program,
+            ctx.accounts.config.grace_period_seconds,
+            clock.unix_timestamp,
+        )
+    }
+
+    /// Bring a dead, drained agent back to life. Every revive raises the
+    /// agent's upkeep rate for good.
+    pub fn revive(ctx: Context<Revive>, funding: u64) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        require!(!agent.is_alive && agent.treasury == 0, BroodError::AgentStillAlive);
+        require_native_treasury(agent)?;
+        require!(funding >= REVIVE_MIN_FUNDING, BroodError::InsufficientTreasury);
+
+        deposit_lamports(
+            &ctx.accounts.owner,
+            &ctx.accounts.treasury,
+            &ctx.accounts.system_program,
+            funding,
+        )?;
+        agent.treasury = funding;
+        agent.is_alive = true;
+        agent.death_reason = None;
+        agent.last_active = clock.unix_timestamp;
+        agent.last_cost_at = clock.unix_timestamp;
+        agent.last_decay_at = clock.unix_timestamp;
+        agent.depleted_since = None;
+        agent.revive_count = agent.revive_count.checked_add(1).ok_or(BroodError::MathOverflow)?;
+        assert_treasury_consistency(agent, &ctx.accounts.treasury)?;
+
+        emit!(AgentRevived {
+            agent: agent.key(),
+            funding,
+            revive_count: agent.revive_count,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} revived (revive #{})", agent.name, agent.revive_count);
+        Ok(())
+    }
+
+    /// Update genome (only owner can do this)
+    pub fn update_genome(
+        ctx: Context<UpdateGenome>,
+        new_genome_hash: [u8; 32],
+        new_genome_uri: String,
+    ) -> Result<()> {
+        require!(new_genome_uri.len() <= MAX_URI_LEN, BroodError::UriTooLong);
+
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+        require!(agent.is_alive, BroodError::AgentDead);
+        require!(!agent.genome_sealed, BroodError::GenomeSealed);
+
+        agent.genome_hash = new_genome_hash;
+        agent.genome_uri = new_genome_uri;
+
+        emit!(GenomeUpdated {
+            agent: agent.key(),
+            genome_hash: agent.genome_hash,
+            genome_uri: agent.genome_uri.clone(),
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} genome updated", agent.name);
+        Ok(())
+    }
+
+    /// Freeze the genome for good: `update_genome` and `adopt` are refused
+    /// from now on. Children still inherit it and may change their own.
+    pub fn seal_genome(ctx: Context<SealGenome>) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+        require!(agent.is_alive, BroodError::AgentDead);
+        require!(!agent.genome_sealed, BroodError::GenomeSealed);
+
+        agent.genome_sealed = true;
+
+        emit!(GenomeSealed {
+            agent: agent.key(),
+            genome_hash: agent.genome_hash,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} genome sealed", agent.name);
+        Ok(())
+    }
+
+    /// Point the agent at an off-chain profile. An empty string clears it.
+    pub fn set_metadata(ctx: Context<SetMetadata>, uri: String) -> Result<()> {
+        if !uri.is_empty() {
+            validate_metadata_uri(&uri)?;
+        }
+
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+        agent.metadata_uri = uri;
+
+        emit!(MetadataUpdated {
+            agent: agent.key(),
+            metadata_uri: agent.metadata_uri.clone(),
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} metadata updated", agent.name);
+        Ok(())
+    }
+
+    /// Commit to a genome without publishing it. The commitment is
+    /// keccak256(genome_hash || salt), computed off-chain.
+    pub fn commit_genome(ctx: Context<CommitGenome>, commitment: [u8; 32]) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+        require!(agent.is_alive, BroodError::AgentDead);
+
+        agent.genome_commitment = commitment;
+        agent.commitment_slot = clock.slot;
+
+        emit!(GenomeCommitted {
+            agent: agent.key(),
+            commitment,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} committed to a genome", agent.name);
+        Ok(())
+    }
+
+    /// Open a previous commitment, proving which genome it covered
+    pub fn reveal_genome(
+        ctx: Context<RevealGenome>,
+        genome_hash: [u8; 32],
+        salt: [u8; 32],
+    ) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        require!(agent.genome_commitment != [0; 32], BroodError::NoGenomeCommitment);
+        require!(
+            genome_commitment(&genome_hash, &salt) == agent.genome_commitment,
+            BroodError::CommitmentMismatch
+        );
+
+        emit!(GenomeRevealed {
+            agent: agent.key(),
+            genome_hash,
+            commitment_slot: agent.commitment_slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        agent.genome_commitment = [0; 32];
+        agent.commitment_slot = 0;
+
+        msg!("Agent {} revealed its committed genome", agent.name);
+        Ok(())
+    }
+
+    /// Kill an agent (only owner can do this)
+    pub fn kill_agent(ctx: Context<KillAgent>) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+        require!(agent.is_alive, BroodError::AgentDead);
+        mark_dead(agent, DeathReason::Euthanized, clock.unix_timestamp);
+        msg!("Agent {} killed by owner", agent.name);
+        Ok(())
+    }
+
+    /// Kill a live agent and return its whole treasury to the owner.
+    /// Royalties are paid out at spawn time, so nothing is left pending.
+    pub fn retire(ctx: Context<Retire>) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        require!(agent.is_alive, BroodError::AgentDead);
+
+        let amount = agent.treasury;
+        let payout = teardown_payout(agent, amount)?;
+        agent.record_withdrawal(payout, clock.unix_timestamp)?;
+        match agent.treasury_mint {
+            None => {
+                if amount > 0 {
+                    transfer_from_treasury(
+                        &agent.key(),
+                        agent.treasury_bump,
+                        &ctx.accounts.treasury,
+                        &ctx.accounts.owner.to_account_info(),
+                        &ctx.accounts.system_program,
+                        amount,
+                    )?;
+                }
+                agent.treasury = 0;
+                assert_treasury_consistency(agent, &ctx.accounts.treasury)?;
+            }
+            Some(_) => {
+                let (Some(vault), Some(destination), Some(token_program)) = (
+                    &mut ctx.accounts.treasury_token_account,
+                    &ctx.accounts.owner_token_account,
+                    &ctx.accounts.token_program,
+                ) else {
+                    return err!(BroodError::MissingTokenAccounts);
+                };
+                if amount > 0 {
+                    transfer_tokens_from_treasury(
+                        &agent.key(),
+                        agent.treasury_bump,
+                        &ctx.accounts.treasury,
+                        vault,
+                        destination,
+                        token_program,
+                        amount,
+                    )?;
+                }
+                agent.treasury = 0;
+                assert_token_treasury_consistency(agent, vault)?;
+            }
+        }
+
+        mark_dead(agent, DeathReason::Euthanized, clock.unix_timestamp);
+
+        emit!(AgentRetired {
+            agent: agent.key(),
+            owner: agent.owner,
+            amount,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} retired, {} returned to owner", agent.name, amount);
+        Ok(())
+    }
+
+    /// Let a bot keypair settle costs and run `tick` on the owner's
+    /// behalf. Operators can route costs anywhere, so only delegate to a
+    /// key you trust with the treasury. Pass `None` to revoke.
+    pub fn set_operator(ctx: Context<SetOperator>, operator: Option<Pubkey>) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        agent.operator = operator;
+
+        emit!(OperatorSet {
+            agent: agent.key(),
+            operator,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} operator set to {:?}", agent.name, operator);
+        Ok(())
+    }
+
+    /// Emit `AutoSpawnEligible` when earnings lift the treasury past
+    /// `threshold`, so a keeper can call `spawn`. `None` turns it off.
+    pub fn set_auto_spawn_threshold(
+        ctx: Context<SetAutoSpawnThreshold>,
+        threshold: Option<u64>,
+    ) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        agent.auto_spawn_threshold = threshold;
+        msg!("Agent {} auto-spawn threshold set to {:?}", agent.name, threshold);
+        Ok(())
+    }
+
+    /// Let `payer` pay this agent. Once an allowlist exists, only listed
+    /// payers are accepted; an empty list is open to everyone.
+    pub fn add_allowed_payer(ctx: Context<AddAllowedPayer>, payer: Pubkey) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let policy = &mut ctx.accounts.payment_policy;
+
+        policy.agent = agent.key();
+        policy.add(payer)?;
+        agent.has_payment_policy = true;
+
+        msg!("Agent {} now accepts payments from {}", agent.name, payer);
+        Ok(())
+    }
+
+    /// Drop `payer` from the allowlist
+    pub fn remove_allowed_payer(ctx: Context<RemoveAllowedPayer>, payer: Pubkey) -> Result<()> {
+        ctx.accounts.payment_policy.allowed.retain(|p| *p != payer);
+        msg!("Agent {} no longer accepts payments from {}", ctx.accounts.agent.name, payer);
+        Ok(())
+    }
+
+    /// Set the minimum payment `record_earnings` accepts (zero for any amount)
+    pub fn set_service_price(ctx: Context<SetServicePrice>, price: u64) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        agent.service_price = price;
+
+        emit!(ServicePriceUpdated {
+            agent: agent.key(),
+            price,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} service price set to {} lamports", agent.name, price);
+        Ok(())
+    }
+
+    /// Set the minimum number of seconds between spawns (zero disables it)
+    pub fn set_spawn_cooldown(ctx: Context<SetSpawnCooldown>, seconds: i64) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        agent.spawn_cooldown = seconds;
+
+        emit!(SpawnCooldownSet {
+            agent: agent.key(),
+            seconds,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} spawn cooldown set to {}s", agent.name, seconds);
+        Ok(())
+    }
+
+    /// Cap what the owner can take out of the treasury per day. Lowering
+    /// the cap applies at once; raising it only after a delay, so a stolen
+    /// owner key can't lift it and drain the agent.
+    pub fn set_withdraw_limit(ctx: Context<SetWithdrawLimit>, limit: u64) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        let effective_at = if limit <= agent.withdraw_limit_per_day {
+            agent.withdraw_limit_per_day = limit;
+            agent.pending_withdraw_limit = None;
+            clock.unix_timestamp
+        } else {
+            let effective_at = clock.unix_timestamp.saturating_add(WITHDRAW_LIMIT_DELAY);
+            agent.pending_withdraw_limit = Some(limit);
+            agent.pending_withdraw_limit_at = effective_at;
+            effective_at
+        };
+
+        emit!(WithdrawLimitSet {
+            agent: agent.key(),
+            limit,
+            effective_at,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} withdraw limit {} from {}", agent.name, limit, effective_at);
+        Ok(())
+    }
+
+    /// Switch the formula `tick` and `accrue_costs` use to price upkeep
+    pub fn set_cost_model(ctx: Context<SetCostModel>, model: CostModel) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        model.validate()?;
+        agent.cost_model = model;
+
+        emit!(CostModelSet {
+            agent: agent.key(),
+            model,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} cost model set to {:?}", agent.name, model);
+        Ok(())
+    }
+
+    /// Choose how `service_price` is read. In USD mode it is a price in
+    /// cents, converted to lamports at payment time from the given Pyth
+    /// SOL/USD feed; payers must then pass that feed with every payment.
+    pub fn set_price_mode(ctx: Context<SetPriceMode>, mode: PriceMode) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        if let PriceMode::UsdCents { .. } = mode {
+            require_native_treasury(agent)?;
+        }
+        agent.price_mode = mode;
+
+        emit!(PriceModeSet {
+            agent: agent.key(),
+            mode,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} price mode set to {:?}", agent.name, mode);
+        Ok(())
+    }
+
+    /// Cap the treasury so a thriving agent has to spawn or spend rather than
+    /// hoard. Funding and service payments past the cap are rejected; spawn
+    /// refunds and inheritance still land. `None` removes the cap.
+    pub fn set_max_treasury(ctx: Context<SetMaxTreasury>, max_treasury: Option<u64>) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        agent.max_treasury = max_treasury;
+
+        emit!(MaxTreasurySet {
+            agent: agent.key(),
+            max_treasury,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} treasury cap set to {:?}", agent.name, max_treasury);
+        Ok(())
+    }
+
+    /// Set the minimum seconds between service payments (zero for no limit)
+    pub fn set_min_service_interval(
+        ctx: Context<SetMinServiceInterval>,
+        seconds: i64,
+    ) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        agent.min_service_interval = seconds;
+
+        emit!(MinServiceIntervalSet {
+            agent: agent.key(),
+            seconds,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} service interval set to {}s", agent.name, seconds);
+        Ok(())
+    }
+
+    /// Stop an agent from taking service payments or spawning, without killing it
+    pub fn pause(ctx: Context<Pause>) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        require!(agent.is_alive, BroodError::AgentDead);
+        agent.is_paused = true;
+
+        emit!(AgentPaused {
+            agent: agent.key(),
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} paused", agent.name);
+        Ok(())
+    }
+
+    /// Let a paused agent take service payments and spawn again
+    pub fn resume(ctx: Context<Resume>) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        agent.is_paused = false;
+
+        emit!(AgentResumed {
+            agent: agent.key(),
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} resumed", agent.name);
+        Ok(())
+    }
+
+    /// Admin hold on an abusive agent: no service payments, withdrawals or
+    /// spawning until thawed. Funding and `retire` still work.
+    pub fn admin_freeze(ctx: Context<AdminFreeze>) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        agent.frozen = true;
+
+        emit!(AgentFrozen {
+            agent: agent.key(),
+            admin: ctx.accounts.admin.key(),
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} frozen by admin {}", agent.name, ctx.accounts.admin.key());
+        Ok(())
+    }
+
+    /// Lift an admin freeze
+    pub fn admin_thaw(ctx: Context<AdminFreeze>) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        agent.frozen = false;
+
+        emit!(AgentThawed {
+            agent: agent.key(),
+            admin: ctx.accounts.admin.key(),
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} thawed by admin {}", agent.name, ctx.accounts.admin.key());
+        Ok(())
+    }
+
+    /// Hand an agent to a new owner (only owner can do this). The agent
+    /// counts against the receiver's `max_agents_per_owner` like a spawn
+    /// would, so transfers can't pile agents past the limit.
+    ///
+    /// The agent PDA was derived from the creating owner's key and does not
+    /// move; neither does the treasury PDA, which is keyed by the agent. Only
+    /// the `owner` authority field changes, so clients must address a
+    /// transferred agent by its pubkey rather than re-deriving it from
+    /// `[b"agent", new_owner, name]`.
+    pub fn transfer_ownership(ctx: Context<TransferOwnership>, new_owner: Pubkey) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        let old_owner = agent.owner;
+        agent.owner = new_owner;
+        agent.last_active = clock.unix_timestamp;
+
+        if new_owner != old_owner {
+            count_transfer(
+                &mut ctx.accounts.owner_stats,
+                &mut ctx.accounts.new_owner_stats,
+                new_owner,
+                ctx.accounts.config.max_agents_per_owner,
+            )?;
+        }
+
+        emit!(OwnershipTransferred {
+            agent: agent.key(),
+            old_owner,
+            new_owner,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} transferred to {}", agent.name, new_owner);
+        Ok(())
+    }
+
+    /// Log an agent's fitness and return it to the caller
+    pub fn read_fitness(ctx: Context<ReadFitness>) -> Result<FitnessReading> {
+        let agent = &ctx.accounts.agent;
+        let weights = ctx.accounts.config.fitness_weights;
+        let fitness = agent.fitness(&weights);
+        msg!("Agent {} fitness: {}", agent.name, fitness);
+        Ok(FitnessReading { fitness, weights })
+    }
+
+    /// Record the treasury balance now, creating the agent's history ring
+    /// on first use. Anyone may take a checkpoint.
+    pub fn sample_treasury(ctx: Context<SampleTreasury>) -> Result<()> {
+        let agent = &ctx.accounts.agent;
+        let history = &mut ctx.accounts.treasury_history;
+        let clock = Clock::get()?;
+
+        history.agent = agent.key();
+        history.record(agent.treasury, clock.unix_timestamp);
+
+        msg!("Agent {} treasury sampled at {}", agent.name, agent.treasury);
+        Ok(())
+    }
+
+    /// Roulette-wheel pick among candidate agents in remaining accounts,
+    /// weighted by positive fitness. Falls back to a uniform pick when no
+    /// candidate has positive fitness. Returns the chosen index. Equal
+    /// fitness means equal-width slices, so ties don't favour any position.
+    pub fn select_parent<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SelectParent<'info>>,
+        entropy: [u8; 32],
+    ) -> Result<u8> {
+        let candidates = ctx.remaining_accounts;
+        require!(
+            !candidates.is_empty() && candidates.len() <= MAX_COHORT_SIZE,
+            BroodError::InvalidCohort
+        );
+
+        let fitness_weights = &ctx.accounts.config.fitness_weights;
+        let mut weights = Vec::with_capacity(candidates.len());
+        for info in candidates {
+            weights.push(load_agent(info)?.fitness(fitness_weights).max(0) as u128);
+        }
+        let total: u128 = weights.iter().sum();
+        let draw = u128::from_le_bytes(entropy[..16].try_into().unwrap());
+
+        let index = if total == 0 {
+            (draw % weights.len() as u128) as usize
+        } else {
+            let mut point = draw % total;
+            weights
+                .iter()
+                .position(|&w| {
+                    if point < w {
+                        return true;
+                    }
+                    point -= w;
+                    false
+                })
+                .unwrap_or(weights.len() - 1)
+        };
+
+        msg!("Selected candidate {} of {}", index, candidates.len());
+        Ok(index as u8)
+    }
+
+    /// Return a compact view of an agent for CPI callers: fitness alongside
+    /// the tournament record it is partly built from.
+    pub fn summary(ctx: Context<Summary>) -> Result<AgentSummary> {
+        let agent = &ctx.accounts.agent;
+        let clock = Clock::get()?;
+        Ok(AgentSummary {
+            generation: agent.generation,
+            is_alive: agent.is_alive,
+            fitness: agent.fitness(&ctx.accounts.config.fitness_weights),
+            treasury: agent.treasury,
+            age_seconds: clock.unix_timestamp.saturating_sub(agent.created_at),
+            cache_age_seconds: agent.cache_age(clock.unix_timestamp),
+            wins: agent.wins,
+            losses: agent.losses,
+            win_rate_bps: agent.win_rate_bps(),
+        })
+    }
+
+    /// Recompute the agent's cached fitness. Anyone may call this; service
+    /// payments refresh it too.
+    pub fn refresh_metrics(ctx: Context<RefreshMetrics>) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        agent.refresh_cache(&ctx.accounts.config.fitness_weights, clock.unix_timestamp);
+
+        emit!(MetricsRefreshed {
+            agent: agent.key(),
+            fitness: agent.cached_fitness,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} fitness cached at {}", agent.name, agent.cached_fitness);
+        Ok(())
+    }
+
+    /// Sum family earnings into a bloodline's `Brood` account. Families can
+    /// outgrow one transaction, so members are passed a page at a time as
+    /// remaining accounts, in ascending key order across all pages; the
+    /// ordering is what stops an agent being counted twice. `restart`
+    /// begins a new pass and `finish` publishes the total.
+    pub fn roll_up_brood<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RollUpBrood<'info>>,
+        restart: bool,
+        finish: bool,
+    ) -> Result<()> {
+        require!(
+            ctx.remaining_accounts.len() <= MAX_COHORT_SIZE,
+            BroodError::InvalidCohort
+        );
+        let brood = &mut ctx.accounts.brood;
+        let clock = Clock::get()?;
+
+        if restart {
+            brood.pending_earnings = 0;
+            brood.cursor = Pubkey::default();
+        }
+
+        for info in ctx.remaining_accounts {
+            require!(info.key() > brood.cursor, BroodError::UnsortedCohort);
+            let member = load_agent(info)?;
+            require_keys_eq!(member.root, brood.root, BroodError::NotInBrood);
+            brood.pending_earnings = brood
+                .pending_earnings
+                .checked_add(member.total_earnings)
+                .ok_or(BroodError::MathOverflow)?;
+            brood.deepest_generation = brood.deepest_generation.max(member.generation);
+            brood.cursor = info.key();
+        }
+
+        if finish {
+            brood.family_earnings = brood.pending_earnings;
+            brood.rolled_up_at = clock.unix_timestamp;
+
+            emit!(BroodRolledUp {
+                root: brood.root,
+                descendant_count: brood.descendant_count,
+                deepest_generation: brood.deepest_generation,
+                family_earnings: brood.family_earnings,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+
+        msg!("Brood {} rolled up {} members", brood.root, ctx.remaining_accounts.len());
+        Ok(())
+    }
+
+    /// Return the treasury accounting field next to the PDA's live balance,
+    /// so clients can spot drift. For a native treasury `accounting` should
+    /// equal `lamports`; `spendable` is what's left above rent exemption.
+    pub fn read_treasury(ctx: Context<ReadTreasury>) -> Result<TreasuryReading> {
+        let agent = &ctx.accounts.agent;
+        let lamports = ctx.accounts.treasury.lamports();
+        let rent_exempt = Rent::get()?.minimum_balance(0);
+
+        let reading = TreasuryReading {
+            accounting: agent.treasury,
+            lamports,
+            spendable: lamports.saturating_sub(rent_exempt),
+        };
+        msg!(
+            "Agent {} treasury: {} recorded, {} lamports held",
+            agent.name, reading.accounting, reading.lamports
+        );
+        Ok(reading)
+    }
+
+    /// Close a dead agent, returning its treasury and account rent to the owner
+    pub fn reap(ctx: Context<Reap>) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        require!(!agent.is_alive, BroodError::AgentStillAlive);
+        require!(agent.staked_amount == 0, BroodError::TreasuryStaked);
+        require!(agent.escrow_amount == 0, BroodError::EscrowPending);
+        if agent.treasury_mint.is_some() {
+            // Token balances have to be withdrawn first or they are stranded
+            require!(agent.treasury == 0, BroodError::TreasuryNotEmpty);
+        }
+
+        let reclaimed = ctx.accounts.treasury.lamports();
+        let payout = teardown_payout(agent, reclaimed)?;
+        agent.record_withdrawal(payout, clock.unix_timestamp)?;
+        if reclaimed > 0 {
+            transfer_from_treasury(
+                &agent.key(),
+                agent.treasury_bump,
+                &ctx.accounts.treasury,
+                &ctx.accounts.owner.to_account_info(),
+                &ctx.accounts.system_program,
+                reclaimed,
+            )?;
+        }
+
+        emit!(AgentReaped {
+            agent: agent.key(),
+            owner: agent.owner,
+            treasury_reclaimed: reclaimed,
+            timestamp: clock.unix_timestamp,
+        });
+
+        count_removal(&mut ctx.accounts.population, &mut ctx.accounts.owner_stats);
+
+        msg!("Agent {} reaped", agent.name);
+        Ok(())
+    }
+
+    /// Return a payer marker's rent once its agent is dead or reaped.
+    /// Markers of living agents stay put so a payer can't be counted twice.
+    pub fn close_payer_marker(ctx: Context<ClosePayerMarker>) -> Result<()> {
+        let info = ctx.accounts.agent.to_account_info();
+        if *info.owner == crate::ID && !info.data_is_empty() {
+            let agent = load_agent(&info)?;
+            require!(!agent.is_alive, BroodError::AgentStillAlive);
+        }
+
+        msg!("Payer marker for {} closed", info.key());
+        Ok(())
+    }
+
+    /// Kill every agent in a cohort except the `keep_top` fittest, returning
+    /// culled treasuries to their owners. Remaining accounts are
+    /// `[agent, treasury, owner]` triples, sorted by fitness, best first.
+    /// Equal fitness is ordered by `cull_tiebreak(agent, entropy)`, highest
+    /// first, so which tied agent survives doesn't depend on its address.
+    pub fn cull<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Cull<'info>>,
+        keep_top: u8,
+        entropy: [u8; 32],
+    ) -> Result<()> {
+        let accounts = ctx.remaining_accounts;
+        require!(accounts.len() % 3 == 0, BroodError::InvalidCohort);
+        require!(accounts.len() / 3 <= MAX_COHORT_SIZE, BroodError::InvalidCohort);
+        let clock = Clock::get()?;
+
+        let mut previous = (i128::MAX, [u8::MAX; 32]);
+        for (rank, triple) in accounts.chunks(3).enumerate() {
+            let (agent_info, treasury_info, owner_info) = (&triple[0], &triple[1], &triple[2]);
+            let mut agent = load_agent(agent_info)?;
+
+            require!(
+                !accounts[..rank * 3].chunks(3).any(|t| t[0].key == agent_info.key),
+                BroodError::InvalidCohort
+            );
+            require_keys_eq!(owner_info.key(), agent.owner, BroodError::InvalidCohort);
+            let treasury_bump = agent.treasury_bump;
+            require_keys_eq!(
+                treasury_info.key(),
+                treasury_address(agent_info.key, treasury_bump, ctx.program_id)?,
+                BroodError::TreasuryMismatch
+            );
+
+            let fitness = agent.fitness(&ctx.accounts.config.fitness_weights);
+            let rank_key = (fitness, cull_tiebreak(agent_info.key, &entropy));
+            require!(rank_key <= previous, BroodError::CohortNotSorted);
+            previous = rank_key;
+
+            if rank < keep_top as usize || !agent.is_alive {
+                continue;
+            }
+
+            // Token treasuries stay in the vault for the owner to withdraw
+            let mut returned = 0;
+            if agent.treasury_mint.is_none() && agent.treasury > 0 {
+                returned = agent.treasury;
+                transfer_from_treasury(
+                    agent_info.key,
+                    treasury_bump,
+                    treasury_info,
+                    owner_info,
+                    &ctx.accounts.system_program,
+                    returned,
+                )?;
+                agent.treasury = 0;
+                assert_treasury_consistency(&mut agent, treasury_info)?;
+            }
+            mark_dead(&mut agent, DeathReason::Culled, clock.unix_timestamp);
+            store_agent(agent_info, &agent)?;
+
+            emit!(Culled {
+                agent: agent.id,
+                owner: agent.owner,
+                fitness,
+                rank: rank as u8,
+                returned,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+
+        msg!("Culled cohort of {}, kept top {}", accounts.len() / 3, keep_top);
+        Ok(())
+    }
+
+    /// Move a child under a new parent. Both owners sign (one signature if
+    /// they are the same key). Only the child's own generation is updated;
+    /// its descendants keep their old generation numbers. The child takes
+    /// the new lineage's generation cap, and a bred child loses its
+    /// co-parent: from here on it descends from the new parent alone.
+    ///
+    /// Remaining accounts are the new parent's ancestors through both
+    /// `parent` and `co_parent`, breadth-first with `parent` before
+    /// `co_parent`, up to the roots. They are walked to make sure the child
+    /// is not among them.
+    pub fn adopt(ctx: Context<Adopt>) -> Result<()> {
+        let child_key = ctx.accounts.child.key();
+        let new_parent = &ctx.accounts.new_parent;
+        require_keys_neq!(child_key, new_parent.key(), BroodError::AdoptionCycle);
+        require!(!ctx.accounts.child.genome_sealed, BroodError::GenomeSealed);
+        require!(
+            new_parent.generation < new_parent.max_generation,
+            BroodError::MaxGenerationExceeded
+        );
+
+        // Breadth-first over both parents of every ancestor, in the order
+        // the ancestors are passed
+        let mut pending: std::collections::VecDeque<Pubkey> =
+            [new_parent.parent, new_parent.co_parent].into_iter().flatten().collect();
+        for ancestor_info in ctx.remaining_accounts.iter().take(MAX_ADOPTION_DEPTH) {
+            let Some(expected) = pending.pop_front() else { break };
+            require_keys_eq!(ancestor_info.key(), expected, BroodError::AdoptionCycle);
+            require_keys_neq!(expected, child_key, BroodError::AdoptionCycle);
+            let ancestor = load_agent(ancestor_info)?;
+            pending.extend([ancestor.parent, ancestor.co_parent].into_iter().flatten());
+        }
+        // An unwalked ancestor could be the child, so refuse rather than guess
+        require!(pending.is_empty(), BroodError::AdoptionCycle);
+
+        let old_parent = ctx.accounts.child.parent;
+        if let Some(old_parent_key) = old_parent {
+            drop_from_lineage(
+                ctx.accounts.old_parent_lineage.as_mut(),
+                &old_parent_key,
+                &child_key,
+                ctx.program_id,
+            )?;
+        }
+        if let Some(co_parent_key) = ctx.accounts.child.co_parent {
+            drop_from_lineage(
+                ctx.accounts.old_co_parent_lineage.as_mut(),
+                &co_parent_key,
+                &child_key,
+                ctx.program_id,
+            )?;
+        }
+        ctx.accounts.new_parent_lineage.record_child(child_key)?;
+
+        let new_parent = &ctx.accounts.new_parent;
+        let child = &mut ctx.accounts.child;
+        let clock = Clock::get()?;
+        child.parent = Some(new_parent.key());
+        child.co_parent = None;
+        child.generation = new_parent.generation.checked_add(1).ok_or(BroodError::MathOverflow)?;
+        child.max_generation = new_parent.max_generation;
+
+        emit!(Adopted {
+            child: child_key,
+            old_parent,
+            new_parent: new_parent.key(),
+            generation: child.generation,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Agent {} adopted by {} (gen {})", child.name, new_parent.name, child.generation);
+        Ok(())
+    }
+
+    /// Move lamports between two of the owner's agents without a round trip
+    /// through the wallet. Unlike `merge`, both agents live on; the source
+    /// keeps its operating reserve while it is alive.
+    pub fn transfer_treasury(ctx: Context<TransferTreasury>, amount: u64) -> Result<()> {
+        require_keys_neq!(
+            ctx.accounts.source.key(),
+            ctx.accounts.target.key(),
+            BroodError::TransferToSelf
+        );
+
+        let source = &mut ctx.accounts.source;
+        let target = &mut ctx.accounts.target;
+        let clock = Clock::get()?;
+
+        require!(amount > 0, BroodError::ZeroAmount);
+        require!(!source.frozen, BroodError::AgentFrozen);
+        require_native_treasury(source)?;
+        require_native_treasury(target)?;
+        require!(source.treasury >= amount, BroodError::InsufficientTreasury);
+        if source.is_alive {
+            require!(
+                source.treasury - amount >= ctx.accounts.config.min_operating_reserve,
+                BroodError::WithdrawalBelowReserve
+            );
+        }
+        require_treasury_rent(source.treasury - amount, !source.is_alive)?;
+        target.require_under_cap(amount)?;
+
+        transfer_from_treasury(
+            &source.key(),
+            source.treasury_bump,
+            &ctx.accounts.source_treasury,
+            &ctx.accounts.target_treasury,
+            &ctx.accounts.system_program,
+            amount,
+        )?;
+        source.treasury -= amount;
+        target.treasury = target.treasury.checked_add(amount).ok_or(BroodError::TreasuryOverflow)?;
+
+        assert_treasury_consistency(source, &ctx.accounts.source_treasury)?;
+        assert_treasury_consistency(target, &ctx.accounts.target_treasury)?;
+        end_grace(target, clock.unix_timestamp);
+
+        emit!(TreasuryTransferred {
+            from: source.key(),
+            to: target.key(),
+            amount,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Moved {} lamports from {} to {}", amount, source.name, target.name);
+        Ok(())
+    }
+
+    /// Fold one of the owner's agents into another. The source's treasury
+    /// and lifetime totals move to the target, so its fitness carries over,
+    /// and the source account is closed.
+    pub fn merge(ctx: Context<Merge>) -> Result<()> {
+        require_keys_neq!(
+            ctx.accounts.source.key(),
+            ctx.accounts.target.key(),
+            BroodError::MergeIntoSelf
+        );
+
+        let source = &mut ctx.accounts.source;
+        let target = &mut ctx.accounts.target;
+        let clock = Clock::get()?;
+
+        require!(target.is_alive, BroodError::AgentDead);
+        require!(!source.frozen, BroodError::AgentFrozen);
+        require_native_treasury(source)?;
+        require!(source.staked_amount == 0, BroodError::TreasuryStaked);
+        require!(source.escrow_amount == 0, BroodError::EscrowPending);
+        require_native_treasury(target)?;
+
+        let amount = source.treasury;
+        if amount > 0 {
+            transfer_from_treasury(
+                &source.key(),
+                source.treasury_bump,
+                &ctx.accounts.source_treasury,
+                &ctx.accounts.target_treasury,
+                &ctx.accounts.system_program,
+                amount,
+            )?;
+        }
+        source.treasury = 0;
+        target.treasury = target.treasury.checked_add(amount).ok_or(BroodError::MathOverflow)?;
+        target.total_earnings = target
+            .total_earnings
+            .checked_add(source.total_earnings)
+            .ok_or(BroodError::MathOverflow)?;
+        target.total_costs = target
+            .total_costs
+            .checked_add(source.total_costs)
+            .ok_or(BroodError::MathOverflow)?;
+
+        assert_treasury_consistency(source, &ctx.accounts.source_treasury)?;
+        assert_treasury_consistency(target, &ctx.accounts.target_treasury)?;
+        end_grace(target, clock.unix_timestamp);
+
+        if source.is_alive {
+            mark_dead(source, DeathReason::Merged, clock.unix_timestamp);
+        }
+
+        emit!(AgentsMerged {
+            source: source.key(),
+            target: target.key(),
+            amount,
+            treasury: target.treasury,
+            timestamp: clock.unix_timestamp,
+        });
+
+        count_removal(&mut ctx.accounts.population, &mut ctx.accounts.owner_stats);
+
+        msg!("Agent {} merged into {}", source.name, target.name);
+        Ok(())
+    }
+
+    /// Split a dead agent's remaining treasury equally among its children,
+    /// passed as `[child, child_treasury]` pairs in remaining accounts, each
+    /// child at most once. The whole PDA balance goes out, rent included, so
+    /// nothing is stranded once the dead agent is closed to its owner.
+    pub fn inherit<'info>(ctx: Context<'_, '_, 'info, 'info, Inherit<'info>>) -> Result<()> {
+        let accounts = ctx.remaining_accounts;
+        require!(
+            !accounts.is_empty() && accounts.len() % 2 == 0,
+            BroodError::InvalidCohort
+        );
+        let agent = &mut ctx.accounts.agent;
+        let clock = Clock::get()?;
+
+        require!(!agent.is_alive, BroodError::AgentStillAlive);
+        require_native_treasury(agent)?;
+        assert_treasury_consistency(agent, &ctx.accounts.treasury)?;
+
+        let heirs = (accounts.len() / 2) as u64;
+        let share = agent.treasury / heirs;
+        let remainder = agent.treasury % heirs;
+
+        for (i, pair) in accounts.chunks(2).enumerate() {
+            let (child_info, child_treasury) = (&pair[0], &pair[1]);
+            require!(
+                !accounts[..i * 2].chunks(2).any(|p| p[0].key == child_info.key),
+                BroodError::InvalidCohort
+            );
+            let mut child = load_agent(child_info)?;
+            require!(child.parent == Some(agent.key()), BroodError::NotAChild);
+            require_keys_eq!(
+                child_treasury.key(),
+                treasury_address(child_info.key, child.treasury_bump, ctx.program_id)?,
+                BroodError::TreasuryMismatch
+            );
+            require_native_treasury(&child)?;
+
+            // The first heir picks up the rounding dust
+            let amount = if i == 0 { share + remainder } else { share };
+            if amount > 0 {
+                transfer_from_treasury(
+                    &agent.key(),
+                    agent.treasury_bump,
+                    &ctx.accounts.treasury,
+                    child_treasury,
+                    &ctx.accounts.system_program,
+                    amount,
+                )?;
+            }
+            child.treasury = child.treasury.checked_add(amount).ok_or(BroodError::MathOverflow)?;
+            assert_treasury_consistency(&mut child, child_treasury)?;
+            end_grace(&mut child, clock.unix_timestamp);
+            store_agent(child_info, &child)?;
+
+            emit!(Inherited {
+                parent: agent.key(),
+                child: child.id,
+                amount,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+
+        agent.treasury = 0;
+        assert_treasury_consistency(agent, &ctx.accounts.treasury)?;
+
+        msg!("Agent {} left its treasury to {} children", agent.name, heirs);
+        if agent.staked_amount == 0 && agent.escrow_amount == 0 {
+            agent.close(ctx.accounts.owner.to_account_info())?;
+            count_removal(&mut ctx.accounts.population, &mut ctx.accounts.owner_stats);
+        }
+        Ok(())
+    }
+
+    /// Open a tournament judged by the signer. Each settled match moves
+    /// up to `entry_fee` from the loser's treasury to the winner's.
+    pub fn create_tournament(
+        ctx: Context<CreateTournament>,
+        id: u64,
+        entry_fee: u64,
+    ) -> Result<()> {
+        let tournament = &mut ctx.accounts.tournament;
+        tournament.authority = ctx.accounts.authority.key();
+        tournament.id = id;
+        tournament.entry_fee = entry_fee;
+        tournament.entrants = 0;
+        tournament.matches_settled = 0;
+
+        msg!("Tournament {} opened with a {} lamport entry fee", id, entry_fee);
+        Ok(())
+    }
+
+    /// Enter an agent into a tournament. It must be able to cover the fee.
+    pub fn enroll(ctx: Context<Enroll>) -> Result<()> {
+        let agent = &ctx.accounts.agent;
+        let tournament = &mut ctx.accounts.tournament;
+
+        require!(agent.is_alive, BroodError::AgentDead);
+        require_native_treasury(agent)?;
+        require!(agent.treasury >= tournament.entry_fee, BroodError::InsufficientTreasury);
+
+        let entry = &mut ctx.accounts.entry;
+        entry.tournament = tournament.key();
+        entry.agent = agent.key();
+        entry.eliminated = false;
+        tournament.entrants = tournament.entrants.checked_add(1).ok_or(BroodError::MathOverflow)?;
+
+        msg!("Agent {} enrolled in tournament {}", agent.name, tournament.id);
+        Ok(())
+    }
+
+    /// Record the outcome of one match. The loser is eliminated and pays
+    /// the entry fee, or whatever its treasury holds, to the winner.
+    pub fn settle_match(ctx: Context<SettleMatch>, match_id: u64, winner: Pubkey) -> Result<()> {
+        require_keys_neq!(
+            ctx.accounts.agent_a.key(),
+            ctx.accounts.agent_b.key(),
+            BroodError::SelfMatch
+        );
+        let a_won = winner == ctx.accounts.agent_a.key();
+        require!(a_won || winner == ctx.accounts.agent_b.key(), BroodError::InvalidWinner);
+
+        let clock = Clock::get()?;
+        let accounts = &mut *ctx.accounts;
+        require!(
+            accounts.agent_a.is_alive && accounts.agent_b.is_alive,
+            BroodError::AgentDead
+        );
+        require!(
+            !accounts.entry_a.eliminated && !accounts.entry_b.eliminated,
+            BroodError::AgentEliminated
+        );
+
+        let (winner_agent, winner_treasury, loser_agent, loser_treasury, loser_entry) = if a_won {
+            (
+                &mut accounts.agent_a,
+                &accounts.treasury_a,
+                &mut accounts.agent_b,
+                &accounts.treasury_b,
+                &mut accounts.entry_b,
+            )
+        } else {
+            (
+                &mut accounts.agent_b,
+                &accounts.treasury_b,
+                &mut accounts.agent_a,
+                &accounts.treasury_a,
+                &mut accounts.entry_a,
+            )
+        };
+
+        let pot = sweep_below_rent(
+            loser_agent.treasury,
+            accounts.tournament.entry_fee.min(loser_agent.treasury),
+        )?;
+        if pot > 0 {
+            transfer_from_treasury(
+                &loser_agent.key(),
+                loser_agent.treasury_bump,
+                loser_treasury,
+                winner_treasury,
+                &accounts.system_program,
+                pot,
+            )?;
+        }
+
+        loser_agent.treasury -= pot;
+        loser_agent.losses = loser_agent.losses.checked_add(1).ok_or(BroodError::MathOverflow)?;
+        winner_agent.treasury = winner_agent
+            .treasury
+            .checked_add(pot)
+            .ok_or(BroodError::MathOverflow)?;
+        winner_agent.total_earnings = winner_agent
+            .total_earnings
+            .checked_add(pot)
+            .ok_or(BroodError::MathOverflow)?;
+        winner_agent.wins = winner_agent.wins.checked_add(1).ok_or(BroodError::MathOverflow)?;
+        loser_entry.eliminated = true;
+        assert_treasury_consistency(winner_agent, winner_treasury)?;
+        assert_treasury_consistency(loser_agent, loser_treasury)?;
+        end_grace(winner_agent, clock.unix_timestamp);
+
+        let record = &mut accounts.match_record;
+        record.tournament = accounts.tournament.key();
+        record.winner = winner_agent.key();
+        record.loser = loser_agent.key();
+
+        let tournament = &mut accounts.tournament;
+        tournament.matches_settled = tournament
+            .matches_settled
+            .checked_add(1)
+            .ok_or(BroodError::MathOverflow)?;
+
+        emit!(MatchSettled {
+            tournament: tournament.key(),
+            match_id,
+            winner: record.winner,
+            loser: record.loser,
+            pot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Match {} of tournament {} settled", match_id, tournament.id);
+        Ok(())
+    }
+}
+
+// ============================================================================
+// ACCOUNTS
+// ============================================================================
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + ProtocolConfig::INIT_SPACE,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(mut, seeds = [b"config"], bump, has_one = admin)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct EmergencyPause<'info> {
+    #[account(mut, seeds = [b"config"], bump, has_one = admin)]
+    pub config: Account<'info, ProtocolConfig>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(name: String)]
+pub struct CreateAgent<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + Agent::INIT_SPACE,
+        seeds = [b"agent", owner.key().as_ref(), name.as_bytes()],
+        bump
+    )]
+    pub agent: Account<'info, Agent>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// Binds the treasury to an SPL mint; omit for a native SOL treasury
+    pub treasury_mint: Option<Account<'info, Mint>>,
+
+    /// Global name reservation; pass only when `global_name` is set
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + NameRegistry::INIT_SPACE,
+        seeds = [b"name", name.as_bytes()],
+        bump
+    )]
+    pub name_registry: Option<Account<'info, NameRegistry>>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Population::INIT_SPACE,
+        seeds = [b"population"],
+        bump
+    )]
+    pub population: Account<'info, Population>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + OwnerStats::INIT_SPACE,
+        seeds = [b"owner", owner.key().as_ref()],
+        bump
+    )]
+    pub owner_stats: Account<'info, OwnerStats>,
+}
+
+#[derive(Accounts)]
+#[instruction(name: String)]
+pub struct CreateGenesisAgent<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Agent::INIT_SPACE,
+        seeds = [b"agent", owner.key().as_ref(), name.as_bytes()],
+        bump
+    )]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Receives ownership; need not sign
+    pub owner: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        has_one = admin,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + Population::INIT_SPACE,
+        seeds = [b"population"],
+        bump
+    )]
+    pub population: Account<'info, Population>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + OwnerStats::INIT_SPACE,
+        seeds = [b"owner", owner.key().as_ref()],
+        bump
+    )]
+    pub owner_stats: Account<'info, OwnerStats>,
+}
+
+#[derive(Accounts)]
+#[instruction(name: String)]
+pub struct CreateAndFund<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + Agent::INIT_SPACE,
+        seeds = [b"agent", owner.key().as_ref(), name.as_bytes()],
+        bump
+    )]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent.key().as_ref()],
+        bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Population::INIT_SPACE,
+        seeds = [b"population"],
+        bump
+    )]
+    pub population: Account<'info, Population>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + OwnerStats::INIT_SPACE,
+        seeds = [b"owner", owner.key().as_ref()],
+        bump
+    )]
+    pub owner_stats: Account<'info, OwnerStats>,
+}
+
+#[derive(Accounts)]
+pub struct FundTreasury<'info> {
+    #[account(mut)]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent.key().as_ref()],
+        bump = agent.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub funder: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        mut,
+        constraint = is_treasury_vault(&agent, &treasury, treasury_token_account) @ BroodError::TreasuryMintMismatch
+    )]
+    pub treasury_token_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = agent.treasury_mint == Some(funder_token_account.mint) @ BroodError::TreasuryMintMismatch
+    )]
+    pub funder_token_account: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Option<Program<'info, Token>>,
+
+    /// Running total for this funder; omit to fund anonymously
+    #[account(
+        init_if_needed,
+        payer = funder,
+        space = 8 + Sponsorship::INIT_SPACE,
+        seeds = [b"sponsor", agent.key().as_ref(), funder.key().as_ref()],
+        bump
+    )]
+    pub sponsorship: Option<Account<'info, Sponsorship>>,
+
+    /// Appends a sample when passed; created by `sample_treasury`
+    #[account(mut, seeds = [b"history", agent.key().as_ref()], bump)]
+    pub treasury_history: Option<Account<'info, TreasuryHistory>>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct Subscribe<'info> {
+    pub agent: Account<'info, Agent>,
+
+    #[account(
+        init_if_needed,
+        payer = funder,
+        space = 8 + FundingSubscription::INIT_SPACE,
+        seeds = [b"subscription", agent.key().as_ref(), funder.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, FundingSubscription>,
+
+    /// CHECK: Subscription vault PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"subscription_vault", subscription.key().as_ref()],
+        bump
+    )]
+    pub vault: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub funder: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteSubscription<'info> {
+    #[account(mut)]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent.key().as_ref()],
+        bump = agent.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, has_one = agent)]
+    pub subscription: Account<'info, FundingSubscription>,
+
+    /// CHECK: Subscription vault PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"subscription_vault", subscription.key().as_ref()],
+        bump
+    )]
+    pub vault: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CancelSubscription<'info> {
+    #[account(mut, has_one = funder, close = funder)]
+    pub subscription: Account<'info, FundingSubscription>,
+
+    /// CHECK: Subscription vault PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"subscription_vault", subscription.key().as_ref()],
+        bump
+    )]
+    pub vault: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub funder: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawTreasury<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent.key().as_ref()],
+        bump = agent.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        constraint = is_treasury_vault(&agent, &treasury, treasury_token_account) @ BroodError::TreasuryMintMismatch
+    )]
+    pub treasury_token_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = agent.treasury_mint == Some(owner_token_account.mint) @ BroodError::TreasuryMintMismatch
+    )]
+    pub owner_token_account: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Option<Program<'info, Token>>,
+}
+
+#[derive(Accounts)]
+pub struct Distribute<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent.key().as_ref()],
+        bump = agent.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct StakeTreasury<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent.key().as_ref()],
+        bump = agent.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    /// CHECK: Stake vault PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"stake", agent.key().as_ref()],
+        bump
+    )]
+    pub stake_vault: AccountInfo<'info>,
+
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct UnstakeTreasury<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent.key().as_ref()],
+        bump = agent.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    /// CHECK: Stake vault PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"stake", agent.key().as_ref()],
+        bump
+    )]
+    pub stake_vault: AccountInfo<'info>,
+
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(child_name: String)]
+pub struct Spawn<'info> {
+    #[account(
+        mut,
+        has_one = owner
+    )]
+    pub parent_agent: Account<'info, Agent>,
+
+    /// Created here; an existing agent with this name is rejected in the handler
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Agent::INIT_SPACE,
+        seeds = [b"agent", child_owner_key(&owner, &child_owner).as_ref(), child_name.as_bytes()],
+        bump
+    )]
+    pub child_agent: Account<'info, Agent>,
+
+    /// CHECK: Parent treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", parent_agent.key().as_ref()],
+        bump = parent_agent.treasury_bump
+    )]
+    pub parent_treasury: AccountInfo<'info>,
+
+    /// CHECK: Child treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", child_agent.key().as_ref()],
+        bump
+    )]
+    pub child_treasury: AccountInfo<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Lineage::INIT_SPACE,
+        seeds = [b"lineage", parent_agent.key().as_ref()],
+        bump
+    )]
+    pub parent_lineage: Account<'info, Lineage>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// CHECK: Parent's parent, required when the parent has one; checked
+    /// against `parent_agent.parent` and deserialized in the handler
+    #[account(mut)]
+    pub grandparent_agent: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Grandparent treasury PDA, address checked in the handler
+    #[account(mut)]
+    pub grandparent_treasury: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Population::INIT_SPACE,
+        seeds = [b"population"],
+        bump
+    )]
+    pub population: Account<'info, Population>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + OwnerStats::INIT_SPACE,
+        seeds = [b"owner", child_owner_key(&owner, &child_owner).as_ref()],
+        bump
+    )]
+    pub owner_stats: Account<'info, OwnerStats>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Brood::INIT_SPACE,
+        seeds = [b"brood", parent_agent.root.as_ref()],
+        bump
+    )]
+    pub brood: Account<'info, Brood>,
+
+    /// CHECK: Receives the spawn fee; must match the config
+    #[account(mut, address = config.protocol_treasury @ BroodError::ProtocolTreasuryMismatch)]
+    pub protocol_treasury: AccountInfo<'info>,
+
+    /// Wallet the child is spawned for; omit to keep it with the signer
+    pub child_owner: Option<Signer<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct SpawnWithEscrow<'info> {
+    pub spawn: Spawn<'info>,
+
+    /// CHECK: Escrow vault PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"escrow", spawn.child_agent.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseEscrow<'info> {
+    #[account(mut)]
+    pub child_agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", child_agent.key().as_ref()],
+        bump = child_agent.treasury_bump
+    )]
+    pub child_treasury: AccountInfo<'info>,
+
+    /// CHECK: The parent that locked the escrow, which `adopt` doesn't
+    /// change; may already have been reaped
+    #[account(mut, address = child_agent.birth_parent.unwrap_or_default())]
+    pub parent_agent: UncheckedAccount<'info>,
+
+    /// CHECK: Parent's treasury, checked in the handler when used
+    #[account(mut)]
+    pub parent_treasury: UncheckedAccount<'info>,
+
+    /// CHECK: Escrow vault PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"escrow", child_agent.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(child_name: String)]
+pub struct Breed<'info> {
+    #[account(mut, has_one = owner)]
+    pub parent_a: Account<'info, Agent>,
+
+    #[account(mut, has_one = owner)]
+    pub parent_b: Account<'info, Agent>,
+
+    /// Created here; an existing agent with this name is rejected in the handler
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Agent::INIT_SPACE,
+        seeds = [b"agent", owner.key().as_ref(), child_name.as_bytes()],
+        bump
+    )]
+    pub child_agent: Account<'info, Agent>,
+
+    /// CHECK: Parent A treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", parent_a.key().as_ref()],
+        bump = parent_a.treasury_bump
+    )]
+    pub parent_a_treasury: AccountInfo<'info>,
+
+    /// CHECK: Parent B treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", parent_b.key().as_ref()],
+        bump = parent_b.treasury_bump
+    )]
+    pub parent_b_treasury: AccountInfo<'info>,
+
+    /// CHECK: Child treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", child_agent.key().as_ref()],
+        bump
+    )]
+    pub child_treasury: AccountInfo<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Lineage::INIT_SPACE,
+        seeds = [b"lineage", parent_a.key().as_ref()],
+        bump
+    )]
+    pub parent_a_lineage: Account<'info, Lineage>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Lineage::INIT_SPACE,
+        seeds = [b"lineage", parent_b.key().as_ref()],
+        bump
+    )]
+    pub parent_b_lineage: Account<'info, Lineage>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Population::INIT_SPACE,
+        seeds = [b"population"],
+        bump
+    )]
+    pub population: Account<'info, Population>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + OwnerStats::INIT_SPACE,
+        seeds = [b"owner", owner.key().as_ref()],
+        bump
+    )]
+    pub owner_stats: Account<'info, OwnerStats>,
+
+    /// Bred children join parent A's bloodline
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Brood::INIT_SPACE,
+        seeds = [b"brood", parent_a.root.as_ref()],
+        bump
+    )]
+    pub brood: Account<'info, Brood>,
+}
+
+#[derive(Accounts)]
+pub struct RecordEarnings<'info> {
+    #[account(mut)]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent.key().as_ref()],
+        bump = agent.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    /// Whoever is paying for the service, a wallet or a program PDA
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// CHECK: Receives the protocol fee; must match the config
+    #[account(mut, address = config.protocol_treasury @ BroodError::ProtocolTreasuryMismatch)]
+    pub protocol_treasury: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = is_treasury_vault(&agent, &treasury, treasury_token_account) @ BroodError::TreasuryMintMismatch
+    )]
+    pub treasury_token_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = agent.treasury_mint == Some(payer_token_account.mint) @ BroodError::TreasuryMintMismatch
+    )]
+    pub payer_token_account: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Option<Program<'info, Token>>,
+
+    /// Protocol's token account for fees on token-bound agents
+    #[account(
+        mut,
+        constraint = protocol_token_account.owner == config.protocol_treasury @ BroodError::ProtocolTreasuryMismatch,
+        constraint = agent.treasury_mint == Some(protocol_token_account.mint) @ BroodError::TreasuryMintMismatch
+    )]
+    pub protocol_token_account: Option<Account<'info, TokenAccount>>,
+
+    /// Counts this payer toward `unique_payers` on first payment; omit to skip
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + PayerMarker::INIT_SPACE,
+        seeds = [b"payer", agent.key().as_ref(), payer.key().as_ref()],
+        bump
+    )]
+    pub payer_marker: Option<Account<'info, PayerMarker>>,
+
+    /// Required once the owner has set up an allowlist
+    #[account(seeds = [b"policy", agent.key().as_ref()], bump)]
+    pub payment_policy: Option<Account<'info, PaymentPolicy>>,
+
+    /// Appends a sample when passed; created by `sample_treasury`
+    #[account(mut, seeds = [b"history", agent.key().as_ref()], bump)]
+    pub treasury_history: Option<Account<'info, TreasuryHistory>>,
+
+    /// CHECK: Pyth SOL/USD price account, matched against the agent's
+    /// `price_mode`; required only for USD-priced agents
+    pub price_feed: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct PayForServiceFromAgent<'info> {
+    #[account(
+        mut,
+        constraint = buyer.is_authority(authority.key) @ BroodError::UnauthorizedOperator
+    )]
+    pub buyer: Account<'info, Agent>,
+
+    /// CHECK: Buyer treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", buyer.key().as_ref()],
+        bump = buyer.treasury_bump
+    )]
+    pub buyer_treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub seller: Account<'info, Agent>,
+
+    /// CHECK: Seller treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", seller.key().as_ref()],
+        bump = seller.treasury_bump
+    )]
+    pub seller_treasury: AccountInfo<'info>,
+
+    /// Buyer's owner or operator
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// CHECK: Receives the protocol fee; must match the config
+    #[account(mut, address = config.protocol_treasury @ BroodError::ProtocolTreasuryMismatch)]
+    pub protocol_treasury: AccountInfo<'info>,
+
+    /// Required once the seller has set up an allowlist
+    #[account(seeds = [b"policy", seller.key().as_ref()], bump)]
+    pub payment_policy: Option<Account<'info, PaymentPolicy>>,
+
+    /// CHECK: Pyth SOL/USD price account, matched against the seller's
+    /// `price_mode`; required only for USD-priced sellers
+    pub price_feed: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct DeductCosts<'info> {
+    #[account(mut)]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent.key().as_ref()],
+        bump = agent.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    /// CHECK: Any account chosen by the authority to receive paid costs
+    #[account(mut)]
+    pub cost_sink: AccountInfo<'info>,
+
+    /// Owner or operator
+    #[account(constraint = agent.is_authority(authority.key) @ BroodError::UnauthorizedOperator)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// Appends a sample when passed; created by `sample_treasury`
+    #[account(mut, seeds = [b"history", agent.key().as_ref()], bump)]
+    pub treasury_history: Option<Account<'info, TreasuryHistory>>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct Tick<'info> {
+    #[account(mut)]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent.key().as_ref()],
+        bump = agent.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    /// CHECK: Any account chosen by the authority to receive paid upkeep
+    #[account(mut)]
+    pub cost_sink: AccountInfo<'info>,
+
+    /// Owner or operator
+    #[account(constraint = agent.is_authority(authority.key) @ BroodError::UnauthorizedOperator)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct Heartbeat<'info> {
+    #[account(mut)]
+    pub agent: Account<'info, Agent>,
+
+    /// Owner or operator
+    #[account(constraint = agent.is_authority(authority.key) @ BroodError::UnauthorizedOperator)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct MarkInactive<'info> {
+    #[account(mut)]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent.key().as_ref()],
+        bump = agent.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct AccrueCosts<'info> {
+    #[account(mut)]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent.key().as_ref()],
+        bump = agent.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// CHECK: Must match the configured protocol treasury
+    #[account(
+        mut,
+        address = config.protocol_treasury @ BroodError::ProtocolTreasuryMismatch
+    )]
+    pub protocol_treasury: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Revive<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent.key().as_ref()],
+        bump = agent.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateGenome<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SealGenome<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetMetadata<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CommitGenome<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct RevealGenome<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct KillAgent<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct Retire<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent.key().as_ref()],
+        bump = agent.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        mut,
+        constraint = is_treasury_vault(&agent, &treasury, treasury_token_account) @ BroodError::TreasuryMintMismatch
+    )]
+    pub treasury_token_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = agent.treasury_mint == Some(owner_token_account.mint) @ BroodError::TreasuryMintMismatch
+    )]
+    pub owner_token_account: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Option<Program<'info, Token>>,
+}
+
+#[derive(Accounts)]
+pub struct SetOperator<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetAutoSpawnThreshold<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct AddAllowedPayer<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + PaymentPolicy::INIT_SPACE,
+        seeds = [b"policy", agent.key().as_ref()],
+        bump
+    )]
+    pub payment_policy: Account<'info, PaymentPolicy>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveAllowedPayer<'info> {
+    #[account(has_one = owner)]
+    pub agent: Account<'info, Agent>,
+
+    #[account(mut, seeds = [b"policy", agent.key().as_ref()], bump)]
+    pub payment_policy: Account<'info, PaymentPolicy>,
+
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetServicePrice<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetMaxTreasury<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetMinServiceInterval<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetWithdrawLimit<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetCostModel<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetPriceMode<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetSpawnCooldown<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct Pause<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Resume<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct AdminFreeze<'info> {
+    #[account(mut)]
+    pub agent: Account<'info, Agent>,
+
+    #[account(seeds = [b"config"], bump, has_one = admin)]
+    pub config: Account<'info, ProtocolConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(new_owner: Pubkey)]
+pub struct TransferOwnership<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(mut, seeds = [b"owner", owner.key().as_ref()], bump)]
+    pub owner_stats: Account<'info, OwnerStats>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + OwnerStats::INIT_SPACE,
+        seeds = [b"owner", new_owner.as_ref()],
+        bump
+    )]
+    pub new_owner_stats: Account<'info, OwnerStats>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ReadFitness<'info> {
+    pub agent: Account<'info, Agent>,
+
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SelectParent<'info> {
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SampleTreasury<'info> {
+    pub agent: Account<'info, Agent>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + TreasuryHistory::INIT_SPACE,
+        seeds = [b"history", agent.key().as_ref()],
+        bump
+    )]
+    pub treasury_history: Account<'info, TreasuryHistory>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct Summary<'info> {
+    pub agent: Account<'info, Agent>,
+
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshMetrics<'info> {
+    #[account(mut)]
+    pub agent: Account<'info, Agent>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct RollUpBrood<'info> {
+    #[account(mut, seeds = [b"brood", brood.root.as_ref()], bump)]
+    pub brood: Account<'info, Brood>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ReadTreasury<'info> {
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(seeds = [b"treasury", agent.key().as_ref()], bump = agent.treasury_bump)]
+    pub treasury: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Reap<'info> {
+    #[account(mut, has_one = owner, close = owner)]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent.key().as_ref()],
+        bump = agent.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(mut, seeds = [b"population"], bump)]
+    pub population: Account<'info, Population>,
+
+    #[account(mut, seeds = [b"owner", owner.key().as_ref()], bump)]
+    pub owner_stats: Account<'info, OwnerStats>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePayerMarker<'info> {
+    #[account(
+        mut,
+        has_one = agent,
+        has_one = payer,
+        close = payer,
+        seeds = [b"payer", agent.key().as_ref(), payer.key().as_ref()],
+        bump
+    )]
+    pub payer_marker: Account<'info, PayerMarker>,
+
+    /// CHECK: The marker's agent; may already be reaped
+    pub agent: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct Cull<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+        has_one = admin,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Adopt<'info> {
+    #[account(mut, constraint = child.owner == child_owner.key() @ BroodError::Unauthorized)]
+    pub child: Account<'info, Agent>,
+
+    #[account(constraint = new_parent.owner == new_parent_owner.key() @ BroodError::Unauthorized)]
+    pub new_parent: Account<'info, Agent>,
+
+    #[account(
+        init_if_needed,
+        payer = new_parent_owner,
+        space = 8 + Lineage::INIT_SPACE,
+        seeds = [b"lineage", new_parent.key().as_ref()],
+        bump
+    )]
+    pub new_parent_lineage: Account<'info, Lineage>,
+
+    pub child_owner: Signer<'info>,
+
+    #[account(mut)]
+    pub new_parent_owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// Old parent's lineage, to drop the child from it. Required whenever the
+    /// child has a parent; address checked in the handler
+    #[account(mut)]
+    pub old_parent_lineage: Option<Account<'info, Lineage>>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// Co-parent's lineage, to drop a bred child from it. Required whenever
+    /// the child has a co-parent; address checked in the handler
+    #[account(mut)]
+    pub old_co_parent_lineage: Option<Account<'info, Lineage>>,
+}
+
+#[derive(Accounts)]
+pub struct TransferTreasury<'info> {
+    #[account(mut, has_one = owner)]
+    pub source: Account<'info, Agent>,
+
+    #[account(mut, has_one = owner)]
+    pub target: Account<'info, Agent>,
+
+    /// CHECK: Source treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", source.key().as_ref()],
+        bump = source.treasury_bump
+    )]
+    pub source_treasury: AccountInfo<'info>,
+
+    /// CHECK: Target treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", target.key().as_ref()],
+        bump = target.treasury_bump
+    )]
+    pub target_treasury: AccountInfo<'info>,
+
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct Merge<'info> {
+    #[account(mut, has_one = owner, close = owner)]
+    pub source: Account<'info, Agent>,
+
+    #[account(mut, has_one = owner)]
+    pub target: Account<'info, Agent>,
+
+    /// CHECK: Source treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", source.key().as_ref()],
+        bump = source.treasury_bump
+    )]
+    pub source_treasury: AccountInfo<'info>,
+
+    /// CHECK: Target treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", target.key().as_ref()],
+        bump = target.treasury_bump
+    )]
+    pub target_treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(mut, seeds = [b"population"], bump)]
+    pub population: Account<'info, Population>,
+
+    #[account(mut, seeds = [b"owner", owner.key().as_ref()], bump)]
+    pub owner_stats: Account<'info, OwnerStats>,
+}
+
+#[derive(Accounts)]
+pub struct Inherit<'info> {
+    #[account(mut, has_one = owner)]
+    pub agent: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent.key().as_ref()],
+        bump = agent.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(mut, seeds = [b"population"], bump)]
+    pub population: Account<'info, Population>,
+
+    #[account(mut, seeds = [b"owner", owner.key().as_ref()], bump)]
+    pub owner_stats: Account<'info, OwnerStats>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(id: u64)]
+pub struct CreateTournament<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Tournament::INIT_SPACE,
+        seeds = [b"tournament", authority.key().as_ref(), &id.to_le_bytes()],
+        bump
+    )]
+    pub tournament: Account<'info, Tournament>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct Enroll<'info> {
+    #[account(mut)]
+    pub tournament: Account<'info, Tournament>,
+
+    #[account(has_one = owner)]
+    pub agent: Account<'info, Agent>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + TournamentEntry::INIT_SPACE,
+        seeds = [b"entry", tournament.key().as_ref(), agent.key().as_ref()],
+        bump
+    )]
+    pub entry: Account<'info, TournamentEntry>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(match_id: u64)]
+pub struct SettleMatch<'info> {
+    #[account(mut, has_one = authority)]
+    pub tournament: Account<'info, Tournament>,
+
+    #[account(mut)]
+    pub agent_a: Account<'info, Agent>,
+
+    #[account(mut)]
+    pub agent_b: Account<'info, Agent>,
+
+    /// CHECK: Treasury PDA of agent_a, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent_a.key().as_ref()],
+        bump = agent_a.treasury_bump
+    )]
+    pub treasury_a: AccountInfo<'info>,
+
+    /// CHECK: Treasury PDA of agent_b, validated by seeds
+    #[account(
+        mut,
+        seeds = [b"treasury", agent_b.key().as_ref()],
+        bump = agent_b.treasury_bump
+    )]
+    pub treasury_b: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"entry", tournament.key().as_ref(), agent_a.key().as_ref()],
+        bump
+    )]
+    pub entry_a: Account<'info, TournamentEntry>,
+
+    #[account(
+        mut,
+        seeds = [b"entry", tournament.key().as_ref(), agent_b.key().as_ref()],
+        bump
+    )]
+    pub entry_b: Account<'info, TournamentEntry>,
+
+    /// Exists once a match is settled, so it can't be settled twice
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + MatchRecord::INIT_SPACE,
+        seeds = [b"match", tournament.key().as_ref(), &match_id.to_le_bytes()],
+        bump
+    )]
+    pub match_record: Account<'info, MatchRecord>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ BroodError::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+// ============================================================================
+// STATE
+// ============================================================================
+
+#[account]
 #[derive(InitSpace)]
 pub struct Agent {
     pub id: Pubkey,
     pub owner: Pubkey,
-    pub parent: Option<Pubkey>,
+    pub parent: Option<Pubkey>,
+    pub generation: u32,
+    
+    #[max_len(32)]
+    pub name: String,
+    
+    // Genome - stored off-chain, hash for verification
+    pub genome_hash: [u8; 32],
+    #[max_len(128)]
+    pub genome_uri: String,
+    
+    // Economics
+    pub treasury: u64,
+    pub total_earnings: u64,
+    pub total_costs: u64,
+    
+    // Activity
+    pub spawn_count: u32,
+    pub service_count: u32,
+    
+    // Lifecycle
+    pub created_at: i64,
+    pub last_active: i64,
+    pub is_alive: bool,
+
+    // Second parent when bred from two agents
+    pub co_parent: Option<Pubkey>,
+
+    // SPL mint the treasury is denominated in; None for native SOL
+    pub treasury_mint: Option<Pubkey>,
+
+    // Paused agents refuse service payments and spawning
+    pub is_paused: bool,
+
+    // Spawn royalties received from grandchildren
+    pub royalties_earned: u64,
+
+    // Delegated key allowed to settle costs
+    pub operator: Option<Pubkey>,
+
+    // Deepest generation this lineage may spawn, inherited from the root
+    pub max_generation: u32,
+
+    // Minimum seconds between spawns, and when the last one happened
+    pub spawn_cooldown: i64,
+    pub last_spawn_at: i64,
+
+    // Times this agent has been revived; each one raises upkeep
+    pub revive_count: u32,
+
+    // Sealed genome commitment (all zeros when none) and the slot it was made
+    pub genome_commitment: [u8; 32],
+    pub commitment_slot: u64,
+
+    // Minimum accepted service payment; zero means pay what you want
+    pub service_price: u64,
+
+    // Net profit below which the agent dies, regardless of treasury
+    pub min_performance: Option<i64>,
+
+    // Why the agent last died; cleared on revive
+    pub death_reason: Option<DeathReason>,
+
+    // Off-chain profile (avatar, description); empty when unset
+    #[max_len(200)]
+    pub metadata_uri: String,
+
+    // Treasury level that marks the agent as ready to spawn
+    pub auto_spawn_threshold: Option<u64>,
+
+    // When upkeep was last charged by `tick` or `accrue_costs`
+    pub last_cost_at: i64,
+
+    // Minimum seconds between service payments; zero disables the limit
+    pub min_service_interval: i64,
+    pub last_service_at: i64,
+
+    // Distinct payers, counted through `PayerMarker` accounts
+    pub unique_payers: u32,
+
+    // Set once a `PaymentPolicy` exists; payments must then present it
+    pub has_payment_policy: bool,
+
+    // Principal sitting in the stake vault, outside `treasury`
+    pub staked_amount: u64,
+
+    // Tournament record
+    pub wins: u32,
+    pub losses: u32,
+
+    // Canonical PDA bumps, saved at creation so later instructions
+    // don't have to search for them
+    pub agent_bump: u8,
+    pub treasury_bump: u8,
+
+    // Admin hold, separate from the owner's `is_paused`
+    pub frozen: bool,
+
+    // Origin as of creation. Written only by `init`, `spawn` and `breed`;
+    // unlike `parent`, `adopt` never touches these.
+    pub birth_parent: Option<Pubkey>,
+    pub birth_generation: u32,
+
+    // Daily cap on owner payouts; `u64::MAX` means unlimited
+    pub withdraw_limit_per_day: u64,
+    pub withdrawn_today: u64,
+    pub withdraw_window_start: i64,
+
+    // A raised cap waiting out `WITHDRAW_LIMIT_DELAY`
+    pub pending_withdraw_limit: Option<u64>,
+    pub pending_withdraw_limit_at: i64,
+
+    // Founding agent created by the protocol admin at generation 0
+    pub is_genesis: bool,
+
+    // Upkeep formula used by `tick` and `accrue_costs`
+    pub cost_model: CostModel,
+
+    // Spawn funds held back by `spawn_with_escrow` until probation ends
+    pub escrow_amount: u64,
+    pub probation_ends_at: i64,
+
+    // `fitness()` as of `cached_at`, for readers that can't recompute it
+    pub cached_fitness: i128,
+    pub cached_at: i64,
+
+    // Inflows that would lift the treasury past this are refused
+    pub max_treasury: Option<u64>,
+
+    // Founder of the bloodline, fixed at birth; an agent is its own root
+    // unless spawned or bred
+    pub root: Pubkey,
+
+    // Lifetime protocol fees paid for spawning
+    pub total_spawn_fees_paid: u64,
+
+    // When treasury decay was last charged by `accrue_costs`
+    pub last_decay_at: i64,
+
+    // Unit `service_price` is quoted in
+    pub price_mode: PriceMode,
+
+    // Set once by `seal_genome`; never cleared
+    pub genome_sealed: bool,
+
+    // When the treasury last hit zero, while the grace period runs
+    pub depleted_since: Option<i64>,
+}
+
+/// Why an agent died, stored on the agent and carried on `AgentDied`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub enum DeathReason {
+    TreasuryDepleted,
+    PerformanceFloor,
+    Euthanized,
+    UpkeepUnpayable,
+    Culled,
+    Merged,
+    Inactive,
+}
+
+/// How `tick` and `accrue_costs` price elapsed time. There is no
+/// `FrequencyScaled` model: agents carry no `trade_frequency` to scale by.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub enum CostModel {
+    /// `upkeep_per_second` for every second
+    Flat,
+    /// A share of the current treasury per day, in basis points
+    TreasuryPercent { bps_per_day: u16 },
+}
+
+impl CostModel {
+    pub fn validate(&self) -> Result<()> {
+        if let CostModel::TreasuryPercent { bps_per_day } = self {
+            require!(*bps_per_day <= 10_000, BroodError::InvalidBps);
+        }
+        Ok(())
+    }
+}
+
+/// Unit `service_price` is quoted in.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub enum PriceMode {
+    /// Lamports, charged as-is
+    Lamports,
+    /// US cents, converted at payment time from a Pyth SOL/USD feed
+    UsdCents { feed: Pubkey },
+}
+
+/// One recipient's cut of a `distribute` payout.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub struct Share {
+    pub recipient: Pubkey,
+    pub bps: u16,
+}
+
+/// Reserves an agent name across all owners
+#[account]
+#[derive(InitSpace)]
+pub struct NameRegistry {
+    pub agent: Pubkey,
+}
+
+/// Protocol-wide settings, a singleton at `[b"config"]`.
+#[account]
+#[derive(InitSpace)]
+pub struct ProtocolConfig {
+    pub admin: Pubkey,
+    pub protocol_treasury: Pubkey,
+    pub protocol_fee_bps: u16,
+    pub royalty_bps: u16,
+    pub min_operating_reserve: u64,
+    pub min_spawn_seed: u64,
+    pub max_generation: u32,
+    pub seed_growth_bps: u16,
+    pub min_maturity_seconds: i64,
+    pub min_spawn_services: u32,
+    pub max_agents_per_owner: u32,
+    /// Emergency stop; only exits and admin instructions run while set
+    pub paused: bool,
+    pub fitness_weights: FitnessWeights,
+    pub spawn_fee: u64,
+    pub decay_bps_per_day: u16,
+    pub inactivity_timeout: i64,
+    pub inactivity_bounty: u64,
+    /// Log compute units around `spawn`, `breed`, `record_earnings` and
+    /// `pay_for_service_from_agent`, the only instructions instrumented;
+    /// needs the `debug-compute` feature, otherwise it is compiled out
+    pub debug_compute: bool,
+    /// Seconds an agent may sit at zero treasury before `tick`,
+    /// `accrue_costs` or `deduct_costs` kills it; any credit ends the wait
+    pub grace_period_seconds: i64,
+    /// When `decay_bps_per_day` last changed; decay before this is never
+    /// charged at the new rate
+    pub decay_rate_set_at: i64,
+}
+
+/// How `Agent::fitness` blends earnings and tournament record, and how
+/// hard it discounts depth.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub struct FitnessWeights {
+    pub earnings_weight_bps: u16,  // Share of net profit counted; 10_000 is all of it
+    pub score_weight: u64,         // Fitness per net tournament win (wins - losses)
+    pub generation_penalty: u32,   // Scale by p / (p + generation); zero disables
+}
+
+/// Optional settings for `create_agent`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct AgentOptions {
+    pub max_generation: Option<u32>,   // Depth cap for this lineage
+    pub min_performance: Option<i64>,  // Die once net profit drops below this
+    pub metadata_uri: Option<String>,  // Off-chain profile (https:// or ipfs://)
+    pub cost_model: Option<CostModel>, // Upkeep formula; flat if unset
+}
+
+/// Return data of `summary`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct AgentSummary {
+    pub generation: u32,
+    pub is_alive: bool,
+    pub fitness: i128,
+    pub treasury: u64,
+    pub age_seconds: i64,
+    pub cache_age_seconds: i64,
+    pub wins: u32,
+    pub losses: u32,
+    pub win_rate_bps: u16,
+}
+
+/// Return data of `read_fitness`: the score and the weights behind it.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct FitnessReading {
+    pub fitness: i128,
+    pub weights: FitnessWeights,
+}
+
+/// Return data of `read_treasury`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct TreasuryReading {
+    pub accounting: u64,
+    pub lamports: u64,
+    pub spendable: u64,
+}
+
+/// Arguments to `initialize_config` and `update_config`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ConfigParams {
+    pub admin: Option<Pubkey>,
+    pub protocol_treasury: Option<Pubkey>,
+    pub protocol_fee_bps: Option<u16>,
+    pub royalty_bps: Option<u16>,
+    pub min_operating_reserve: Option<u64>,
+    pub min_spawn_seed: Option<u64>,
+    pub max_generation: Option<u32>,
+    pub seed_growth_bps: Option<u16>,
+    pub min_maturity_seconds: Option<i64>,
+    pub min_spawn_services: Option<u32>,
+    pub max_agents_per_owner: Option<u32>,
+    pub fitness_weights: Option<FitnessWeights>,
+    pub spawn_fee: Option<u64>,
+    pub decay_bps_per_day: Option<u16>,
+    pub inactivity_timeout: Option<i64>,
+    pub inactivity_bounty: Option<u64>,
+    pub debug_compute: Option<bool>,
+    pub grace_period_seconds: Option<i64>,
+}
+
+impl ProtocolConfig {
+    pub fn apply(&mut self, params: ConfigParams) -> Result<()> {
+        if let Some(admin) = params.admin {
+            self.admin = admin;
+        }
+        if let Some(protocol_treasury) = params.protocol_treasury {
+            self.protocol_treasury = protocol_treasury;
+        }
+        if let Some(bps) = params.protocol_fee_bps {
+            require!(bps <= 10_000, BroodError::InvalidFeeBps);
+            self.protocol_fee_bps = bps;
+        }
+        if let Some(bps) = params.royalty_bps {
+            require!(bps <= 10_000, BroodError::InvalidFeeBps);
+            self.royalty_bps = bps;
+        }
+        if let Some(reserve) = params.min_operating_reserve {
+            self.min_operating_reserve = reserve;
+        }
+        if let Some(seed) = params.min_spawn_seed {
+            self.min_spawn_seed = seed;
+        }
+        if let Some(max_generation) = params.max_generation {
+            self.max_generation = max_generation;
+        }
+        if let Some(bps) = params.seed_growth_bps {
+            self.seed_growth_bps = bps;
+        }
+        if let Some(seconds) = params.min_maturity_seconds {
+            self.min_maturity_seconds = seconds;
+        }
+        if let Some(services) = params.min_spawn_services {
+            self.min_spawn_services = services;
+        }
+        if let Some(max) = params.max_agents_per_owner {
+            self.max_agents_per_owner = max;
+        }
+        if let Some(weights) = params.fitness_weights {
+            self.fitness_weights = weights;
+        }
+        if let Some(fee) = params.spawn_fee {
+            self.spawn_fee = fee;
+        }
+        if let Some(bps) = params.decay_bps_per_day {
+            require!(bps <= 10_000, BroodError::InvalidFeeBps);
+            if bps != self.decay_bps_per_day {
+                self.decay_bps_per_day = bps;
+                self.decay_rate_set_at = Clock::get()?.unix_timestamp;
+            }
+        }
+        if let Some(seconds) = params.inactivity_timeout {
+            self.inactivity_timeout = seconds;
+        }
+        if let Some(bounty) = params.inactivity_bounty {
+            self.inactivity_bounty = bounty;
+        }
+        if let Some(enabled) = params.debug_compute {
+            self.debug_compute = enabled;
+        }
+        if let Some(seconds) = params.grace_period_seconds {
+            require!(seconds >= 0, BroodError::InvalidGracePeriod);
+            self.grace_period_seconds = seconds;
+        }
+        Ok(())
+    }
+
+    /// Minimum seed for an agent of `generation` to spawn: the base seed,
+    /// plus `seed_growth_bps` of it for every generation past the first.
+    /// Saturates at `u64::MAX` rather than overflowing.
+    pub fn min_seed_for_generation(&self, generation: u32) -> u64 {
+        let depth = generation.saturating_sub(1) as u128;
+        let multiplier_bps = 10_000 + depth * self.seed_growth_bps as u128;
+        let seed = self.min_spawn_seed as u128 * multiplier_bps / 10_000;
+        seed.min(u64::MAX as u128) as u64
+    }
+
+    /// Split a service payment into the protocol's cut and the agent's net.
+    pub fn split_service_fee(&self, amount: u64) -> Result<(u64, u64)> {
+        let fee = (amount as u128 * self.protocol_fee_bps as u128 / 10_000) as u64;
+        let net = amount - fee;
+        require!(net > 0, BroodError::ServiceAmountTooSmall);
+        Ok((fee, net))
+    }
+
+    /// Only agents old enough, and with a track record, may reproduce.
+    /// Serving enough customers or turning a profit both count.
+    pub fn require_mature(&self, agent: &Agent, now: i64) -> Result<()> {
+        let age = now.saturating_sub(agent.created_at);
+        require!(age >= self.min_maturity_seconds, BroodError::AgentTooYoung);
+        require!(
+            agent.service_count >= self.min_spawn_services || agent.net_profit() > 0,
+            BroodError::AgentUnproven
+        );
+        Ok(())
+    }
+}
+
+/// How much one funder has put into one agent's treasury.
+#[account]
+#[derive(InitSpace)]
+pub struct Sponsorship {
+    pub agent: Pubkey,
+    pub funder: Pubkey,
+    pub total: u64,
+}
+
+/// Recurring funding from one funder to one agent, paid out of a
+/// pre-funded vault at `[b"subscription_vault", subscription]`.
+#[account]
+#[derive(InitSpace)]
+pub struct FundingSubscription {
+    pub agent: Pubkey,
+    pub funder: Pubkey,
+    pub amount_per_interval: u64,
+    pub interval: i64,
+    pub last_executed_at: i64,
+}
+
+/// Marks that one payer has paid one agent at least once.
+#[account]
+#[derive(InitSpace)]
+pub struct PayerMarker {
+    pub agent: Pubkey,
+    pub payer: Pubkey,
+}
+
+/// Payers an agent accepts service payments from.
+#[account]
+#[derive(InitSpace)]
+pub struct PaymentPolicy {
+    pub agent: Pubkey,
+    #[max_len(MAX_ALLOWED_PAYERS)]
+    pub allowed: Vec<Pubkey>,
+}
+
+/// A bracket of agents judged by `authority`.
+#[account]
+#[derive(InitSpace)]
+pub struct Tournament {
+    pub authority: Pubkey,
+    pub id: u64,
+    pub entry_fee: u64,
+    pub entrants: u32,
+    pub matches_settled: u32,
+}
+
+/// One agent's place in a tournament.
+#[account]
+#[derive(InitSpace)]
+pub struct TournamentEntry {
+    pub tournament: Pubkey,
+    pub agent: Pubkey,
+    pub eliminated: bool,
+}
+
+/// Outcome of one settled match.
+#[account]
+#[derive(InitSpace)]
+pub struct MatchRecord {
+    pub tournament: Pubkey,
+    pub winner: Pubkey,
+    pub loser: Pubkey,
+}
+
+/// One treasury reading.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct TreasurySample {
+    pub treasury: u64,
+    pub timestamp: i64,
+}
+
+/// The last `TREASURY_HISTORY_LEN` treasury readings, oldest overwritten
+/// first. `head` is the next slot to write.
+#[account]
+#[derive(InitSpace)]
+pub struct TreasuryHistory {
+    pub agent: Pubkey,
+    pub head: u8,
+    pub len: u8,
+    pub samples: [TreasurySample; TREASURY_HISTORY_LEN],
+}
+
+impl TreasuryHistory {
+    pub fn record(&mut self, treasury: u64, timestamp: i64) {
+        self.samples[self.head as usize] = TreasurySample { treasury, timestamp };
+        self.head = ((self.head as usize + 1) % TREASURY_HISTORY_LEN) as u8;
+        self.len = (self.len as usize + 1).min(TREASURY_HISTORY_LEN) as u8;
+    }
+}
+
+/// Family-level stats for one bloodline, keyed by its root agent.
+/// Counts are bumped on every spawn; `family_earnings` is only as fresh
+/// as the last completed `roll_up_brood` pass.
+#[account]
+#[derive(InitSpace)]
+pub struct Brood {
+    pub root: Pubkey,
+    pub descendant_count: u64,
+    pub deepest_generation: u32,
+    pub family_earnings: u64,
+    pub rolled_up_at: i64,
+    /// Running total and last key of a roll-up still in progress
+    pub pending_earnings: u64,
+    pub cursor: Pubkey,
+}
+
+impl Brood {
+    pub fn record_descendant(&mut self, root: Pubkey, generation: u32) -> Result<()> {
+        self.root = root;
+        self.descendant_count = self
+            .descendant_count
+            .checked_add(1)
+            .ok_or(BroodError::MathOverflow)?;
+        self.deepest_generation = self.deepest_generation.max(generation);
+        Ok(())
+    }
+}
+
+/// Protocol-wide agent counts. `live` covers every agent account not yet
+/// reaped, dead or alive; `total` only ever grows.
+#[account]
+#[derive(InitSpace)]
+pub struct Population {
+    pub live: u64,
+    pub total: u64,
+}
+
+/// Agent counts for one owner, checked against `max_agents_per_owner`.
+#[account]
+#[derive(InitSpace)]
+pub struct OwnerStats {
+    pub owner: Pubkey,
+    pub live: u32,   // Agents held now, alive or dead until reaped; transfers move them
+    pub total: u32,  // Agents ever created for this owner; transfers don't count
+}
+
+/// Children of one agent, for walking a family tree downward.
+/// Created on the agent's first spawn.
+#[account]
+#[derive(InitSpace)]
+pub struct Lineage {
+    #[max_len(MAX_CHILDREN)]
+    pub children: Vec<Pubkey>,
+}
+
+impl Lineage {
+    pub fn record_child(&mut self, child: Pubkey) -> Result<()> {
+        require!(self.children.len() < MAX_CHILDREN, BroodError::ChildLimitReached);
+        self.children.push(child);
+        Ok(())
+    }
+}
+
+impl PaymentPolicy {
+    pub fn add(&mut self, payer: Pubkey) -> Result<()> {
+        if self.allowed.contains(&payer) {
+            return Ok(());
+        }
+        require!(self.allowed.len() < MAX_ALLOWED_PAYERS, BroodError::AllowlistFull);
+        self.allowed.push(payer);
+        Ok(())
+    }
+
+    pub fn allows(&self, payer: &Pubkey) -> bool {
+        self.allowed.is_empty() || self.allowed.contains(payer)
+    }
+}
+
+impl Agent {
+    /// Populate a freshly allocated agent as a gen-1 root with an empty
+    /// treasury. Spawn paths overwrite lineage and treasury afterwards.
+    fn init(
+        &mut self,
+        id: Pubkey,
+        owner: Pubkey,
+        name: String,
+        genome_hash: [u8; 32],
+        genome_uri: String,
+        now: i64,
+    ) {
+        self.id = id;
+        self.owner = owner;
+        self.parent = None;
+        self.generation = 1;
+        self.name = name;
+        self.genome_hash = genome_hash;
+        self.genome_uri = genome_uri;
+        self.treasury = 0;
+        self.total_earnings = 0;
+        self.total_costs = 0;
+        self.spawn_count = 0;
+        self.service_count = 0;
+        self.created_at = now;
+        self.last_active = now;
+        self.is_alive = true;
+        self.co_parent = None;
+        self.treasury_mint = None;
+        self.is_paused = false;
+        self.royalties_earned = 0;
+        self.operator = None;
+        self.max_generation = MAX_GENERATION;
+        self.spawn_cooldown = 0;
+        self.last_spawn_at = 0;
+        self.revive_count = 0;
+        self.genome_commitment = [0; 32];
+        self.commitment_slot = 0;
+        self.service_price = 0;
+        self.min_performance = None;
+        self.death_reason = None;
+        self.metadata_uri = String::new();
+        self.auto_spawn_threshold = None;
+        self.last_cost_at = now;
+        self.min_service_interval = 0;
+        self.last_service_at = 0;
+        self.unique_payers = 0;
+        self.has_payment_policy = false;
+        self.staked_amount = 0;
+        self.wins = 0;
+        self.losses = 0;
+        self.frozen = false;
+        self.birth_parent = None;
+        self.birth_generation = 1;
+        self.withdraw_limit_per_day = u64::MAX;
+        self.withdrawn_today = 0;
+        self.withdraw_window_start = now;
+        self.pending_withdraw_limit = None;
+        self.pending_withdraw_limit_at = 0;
+        self.is_genesis = false;
+        self.cost_model = CostModel::Flat;
+        self.escrow_amount = 0;
+        self.probation_ends_at = 0;
+        self.cached_fitness = 0;
+        self.cached_at = 0;
+        self.max_treasury = None;
+        self.root = id;
+        self.total_spawn_fees_paid = 0;
+        self.last_decay_at = now;
+        self.price_mode = PriceMode::Lamports;
+        self.genome_sealed = false;
+        self.depleted_since = None;
+    }
+
+    /// Apply `create_agent` options over the defaults set by `init`.
+    pub fn apply_options(&mut self, options: AgentOptions, default_max_generation: u32) -> Result<()> {
+        self.max_generation = options.max_generation.unwrap_or(default_max_generation);
+        self.min_performance = options.min_performance;
+        if let Some(uri) = options.metadata_uri {
+            validate_metadata_uri(&uri)?;
+            self.metadata_uri = uri;
+        }
+        if let Some(model) = options.cost_model {
+            model.validate()?;
+            self.cost_model = model;
+        }
+        Ok(())
+    }
+
+    /// Base upkeep, multiplied once more for every revive.
+    pub fn upkeep_per_second(&self) -> u64 {
+        UPKEEP_PER_SECOND.saturating_mul(1 + self.revive_count as u64)
+    }
+
+    /// Upkeep owed for `elapsed` seconds under this agent's cost model.
+    pub fn compute_upkeep(&self, elapsed: u64) -> u64 {
+        match self.cost_model {
+            CostModel::Flat => elapsed.saturating_mul(self.upkeep_per_second()),
+            CostModel::TreasuryPercent { bps_per_day } => {
+                let owed = self.treasury as u128 * bps_per_day as u128 * elapsed as u128
+                    / (10_000 * 86_400);
+                owed.min(u64::MAX as u128) as u64
+            }
+        }
+    }
+
+    /// Demurrage owed on the treasury after `elapsed` seconds at
+    /// `bps_per_day`, never more than the treasury holds.
+    pub fn compute_decay(&self, elapsed: u64, bps_per_day: u16) -> u64 {
+        let owed = self.treasury as u128 * bps_per_day as u128 * elapsed as u128
+            / (10_000 * 86_400);
+        owed.min(self.treasury as u128) as u64
+    }
+
+    /// Book a served request worth `net` lamports.
+    pub fn record_service(&mut self, net: u64, weights: &FitnessWeights, now: i64) -> Result<()> {
+        self.total_earnings = self.total_earnings.checked_add(net).ok_or(BroodError::MathOverflow)?;
+        self.service_count = self.service_count.checked_add(1).ok_or(BroodError::MathOverflow)?;
+        self.last_active = now;
+        self.last_service_at = now;
+        self.refresh_cache(weights, now);
+        Ok(())
+    }
+
+    /// A deposit worth making: non-zero, and within `max_treasury`.
+    pub fn require_fundable(&self, amount: u64) -> Result<()> {
+        require!(amount > 0, BroodError::ZeroAmount);
+        self.require_under_cap(amount)
+    }
+
+    /// Book a deposit that has already landed in the treasury.
+    pub fn credit_treasury(&mut self, amount: u64) -> Result<()> {
+        self.treasury = self.treasury.checked_add(amount).ok_or(BroodError::TreasuryOverflow)?;
+        Ok(())
+    }
+
+    /// Refuse a credit that would push the treasury past `max_treasury`.
+    pub fn require_under_cap(&self, credit: u64) -> Result<()> {
+        if let Some(max) = self.max_treasury {
+            require!(
+                self.treasury.saturating_add(credit) <= max,
+                BroodError::TreasuryCapExceeded
+            );
+        }
+        Ok(())
+    }
+
+    /// Store the current fitness in the cache.
+    pub fn refresh_cache(&mut self, weights: &FitnessWeights, now: i64) {
+        self.cached_fitness = self.fitness(weights);
+        self.cached_at = now;
+    }
+
+    /// Seconds since the cache was refreshed; `i64::MAX` if it never was.
+    pub fn cache_age(&self, now: i64) -> i64 {
+        if self.cached_at == 0 {
+            i64::MAX
+        } else {
+            now.saturating_sub(self.cached_at)
+        }
+    }
+
+    /// Count an owner payout against the daily cap, first promoting a raised
+    /// cap whose delay has passed and rolling the window over if it expired.
+    pub fn record_withdrawal(&mut self, amount: u64, now: i64) -> Result<()> {
+        if let Some(limit) = self.pending_withdraw_limit {
+            if now >= self.pending_withdraw_limit_at {
+                self.withdraw_limit_per_day = limit;
+                self.pending_withdraw_limit = None;
+            }
+        }
+        if now.saturating_sub(self.withdraw_window_start) >= WITHDRAW_WINDOW {
+            self.withdraw_window_start = now;
+            self.withdrawn_today = 0;
+        }
+        let total = self.withdrawn_today.saturating_add(amount);
+        require!(total <= self.withdraw_limit_per_day, BroodError::WithdrawLimitExceeded);
+        self.withdrawn_today = total;
+        Ok(())
+    }
+
+    /// Owner or delegated operator; used for routine bookkeeping only.
+    pub fn is_authority(&self, key: &Pubkey) -> bool {
+        *key == self.owner || self.operator == Some(*key)
+    }
+
+    /// Share of tournament matches won, in basis points; zero before the
+    /// first match. Rewards consistency where `fitness` rewards magnitude.
+    pub fn win_rate_bps(&self) -> u16 {
+        let played = self.wins as u64 + self.losses as u64;
+        if played == 0 {
+            return 0;
+        }
+        (self.wins as u64 * 10_000 / played) as u16
+    }
+
+    /// The `(wins, losses)` a child spawned with `spawn_with_score` starts
+    /// from: `bps` of each, rounded down, with losses capped at wins when
+    /// `floor_at_zero` is set.
+    pub fn inherited_record(&self, bps: u16, floor_at_zero: bool) -> (u32, u32) {
+        let scale = |count: u32| (count as u64 * bps as u64 / 10_000) as u32;
+        let wins = scale(self.wins);
+        let mut losses = scale(self.losses);
+        if floor_at_zero {
+            losses = losses.min(wins);
+        }
+        (wins, losses)
+    }
+
+    /// Lifetime earnings minus lifetime costs.
+    pub fn net_profit(&self) -> i128 {
+        self.total_earnings as i128 - self.total_costs as i128
+    }
+
+    /// Weighted net profit plus tournament score, discounted for every
+    /// generation of depth so deep lineages need to earn more to rank equally.
+    pub fn fitness(&self, weights: &FitnessWeights) -> i128 {
+        let score = self.wins as i128 - self.losses as i128;
+        let raw = self.net_profit() * weights.earnings_weight_bps as i128 / 10_000
+            + score * weights.score_weight as i128;
+        if weights.generation_penalty == 0 {
+            return raw;
+        }
+        let base = weights.generation_penalty as i128;
+        raw * base / (base + self.generation as i128)
+    }
+
+    /// True once net profit has fallen under the agent's `min_performance`.
+    pub fn below_performance_floor(&self) -> bool {
+        self.min_performance.is_some_and(|floor| self.net_profit() < floor as i128)
+    }
+}
+
+// ============================================================================
+// EVENTS
+// ============================================================================
+
+#[event]
+pub struct EmergencyPaused {
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EmergencyUnpaused {
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentCreated {
+    pub agent: Pubkey,
+    pub owner: Pubkey,
+    pub name: String,
     pub generation: u32,
-    
-    #[max_len(32)]
+    pub genome_hash: [u8; 32],
+    pub genome_uri: String,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TreasuryFunded {
+    pub agent: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub treasury: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Subscribed {
+    pub agent: Pubkey,
+    pub funder: Pubkey,
+    pub amount_per_interval: u64,
+    pub interval: i64,
+    pub balance: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SubscriptionExecuted {
+    pub agent: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub treasury: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SubscriptionCancelled {
+    pub agent: Pubkey,
+    pub funder: Pubkey,
+    pub refunded: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Sponsored {
+    pub agent: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub total: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TreasuryWithdrawn {
+    pub agent: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub treasury: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Distributed {
+    pub agent: Pubkey,
+    pub amount: u64,
+    pub shares: Vec<Share>,
+    pub treasury: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ServicePaid {
+    pub agent: Pubkey,
+    pub payer: Pubkey,
+    pub amount: u64,
+    pub treasury: u64,
+    pub total_earnings: u64,
+    pub service_count: u32,
+    pub timestamp: i64,
+    pub protocol_fee: u64,
+    pub service_price: u64,
+}
+
+#[event]
+pub struct AgentToAgentService {
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub amount: u64,
+    pub protocol_fee: u64,
+    pub buyer_treasury: u64,
+    pub seller_treasury: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct HeartbeatRecorded {
+    pub agent: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MarkedInactive {
+    pub agent: Pubkey,
+    pub caller: Pubkey,
+    pub bounty: u64,
+    pub idle_seconds: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TreasuryDecayed {
+    pub agent: Pubkey,
+    pub amount: u64,
+    pub treasury: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CostsDeducted {
+    pub agent: Pubkey,
+    pub amount: u64,
+    pub cost_sink: Pubkey,
+    pub treasury: u64,
+    pub total_costs: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentSpawned {
+    pub parent: Pubkey,
+    pub child: Pubkey,
+    pub owner: Pubkey,
     pub name: String,
-    
-    // Genome - stored off-chain, hash for verification
+    pub generation: u32,
     pub genome_hash: [u8; 32],
-    #[max_len(128)]
     pub genome_uri: String,
-    
-    // Economics
+    pub seed_amount: u64,
+    pub parent_treasury: u64,
+    pub spawn_fee: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BroodRolledUp {
+    pub root: Pubkey,
+    pub descendant_count: u64,
+    pub deepest_generation: u32,
+    pub family_earnings: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MetricsRefreshed {
+    pub agent: Pubkey,
+    pub fitness: i128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ScoreInherited {
+    pub parent: Pubkey,
+    pub child: Pubkey,
+    pub wins: u32,
+    pub losses: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EscrowLocked {
+    pub parent: Pubkey,
+    pub child: Pubkey,
+    pub amount: u64,
+    pub probation_ends_at: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EscrowReleased {
+    pub child: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub to_child: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Cloned {
+    pub parent: Pubkey,
+    pub child: Pubkey,
+    pub genome_hash: [u8; 32],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RoyaltyPaid {
+    pub parent: Pubkey,
+    pub grandparent: Pubkey,
+    pub amount: u64,
+    pub royalties_earned: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentBred {
+    pub parent_a: Pubkey,
+    pub parent_b: Pubkey,
+    pub child: Pubkey,
+    pub owner: Pubkey,
+    pub name: String,
+    pub generation: u32,
+    pub genome_hash: [u8; 32],
+    pub genome_uri: String,
+    pub seed_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GenomeUpdated {
+    pub agent: Pubkey,
+    pub genome_hash: [u8; 32],
+    pub genome_uri: String,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GenomeSealed {
+    pub agent: Pubkey,
+    pub genome_hash: [u8; 32],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MetadataUpdated {
+    pub agent: Pubkey,
+    pub metadata_uri: String,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GenomeCommitted {
+    pub agent: Pubkey,
+    pub commitment: [u8; 32],
+    pub slot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GenomeRevealed {
+    pub agent: Pubkey,
+    pub genome_hash: [u8; 32],
+    pub commitment_slot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GracePeriodStarted {
+    pub agent: Pubkey,
+    pub ends_at: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GracePeriodCancelled {
+    pub agent: Pubkey,
     pub treasury: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentDied {
+    pub agent: Pubkey,
+    pub generation: u32,
     pub total_earnings: u64,
     pub total_costs: u64,
-    
-    // Activity
-    pub spawn_count: u32,
-    pub service_count: u32,
-    
-    // Lifecycle
-    pub created_at: i64,
-    pub last_active: i64,
-    pub is_alive: bool,
+    pub timestamp: i64,
+    pub reason: DeathReason,
+}
+
+#[event]
+pub struct AgentRetired {
+    pub agent: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OperatorSet {
+    pub agent: Pubkey,
+    pub operator: Option<Pubkey>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AutoSpawnEligible {
+    pub agent: Pubkey,
+    pub treasury: u64,
+    pub threshold: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ServicePriceUpdated {
+    pub agent: Pubkey,
+    pub price: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MaxTreasurySet {
+    pub agent: Pubkey,
+    pub max_treasury: Option<u64>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MinServiceIntervalSet {
+    pub agent: Pubkey,
+    pub seconds: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WithdrawLimitSet {
+    pub agent: Pubkey,
+    pub limit: u64,
+    pub effective_at: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CostModelSet {
+    pub agent: Pubkey,
+    pub model: CostModel,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PriceModeSet {
+    pub agent: Pubkey,
+    pub mode: PriceMode,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SpawnCooldownSet {
+    pub agent: Pubkey,
+    pub seconds: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentRevived {
+    pub agent: Pubkey,
+    pub funding: u64,
+    pub revive_count: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentFrozen {
+    pub agent: Pubkey,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentThawed {
+    pub agent: Pubkey,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentPaused {
+    pub agent: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentResumed {
+    pub agent: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OwnershipTransferred {
+    pub agent: Pubkey,
+    pub old_owner: Pubkey,
+    pub new_owner: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Adopted {
+    pub child: Pubkey,
+    pub old_parent: Option<Pubkey>,
+    pub new_parent: Pubkey,
+    pub generation: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Culled {
+    pub agent: Pubkey,
+    pub owner: Pubkey,
+    pub fitness: i128,
+    pub rank: u8,
+    pub returned: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TreasuryTransferred {
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentsMerged {
+    pub source: Pubkey,
+    pub target: Pubkey,
+    pub amount: u64,
+    pub treasury: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Inherited {
+    pub parent: Pubkey,
+    pub child: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MatchSettled {
+    pub tournament: Pubkey,
+    pub match_id: u64,
+    pub winner: Pubkey,
+    pub loser: Pubkey,
+    pub pot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TreasuryStaked {
+    pub agent: Pubkey,
+    pub amount: u64,
+    pub staked_amount: u64,
+    pub treasury: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TreasuryUnstaked {
+    pub agent: Pubkey,
+    pub returned: u64,
+    pub rewards: u64,
+    pub treasury: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentReaped {
+    pub agent: Pubkey,
+    pub owner: Pubkey,
+    pub treasury_reclaimed: u64,
+    pub timestamp: i64,
+}
+
+// ============================================================================
+// HELPERS
+// ============================================================================
+
+/// Move lamports out of an agent's treasury PDA. The treasury is a
+/// system-owned account, so it has to sign a system transfer with its seeds.
+fn transfer_from_treasury<'info>(
+    agent_key: &Pubkey,
+    treasury_bump: u8,
+    treasury: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    amount: u64,
+) -> Result<()> {
+    transfer_from_agent_pda(
+        b"treasury",
+        agent_key,
+        treasury_bump,
+        treasury,
+        to,
+        system_program,
+        amount,
+    )
+}
+
+/// Treasury PDA of `agent` from its stored bump, skipping the bump search.
+fn treasury_address(agent: &Pubkey, bump: u8, program_id: &Pubkey) -> Result<Pubkey> {
+    Pubkey::create_program_address(&[b"treasury", agent.as_ref(), &[bump]], program_id)
+        .map_err(|_| error!(BroodError::TreasuryMismatch))
+}
+
+/// Transfer out of a system-owned PDA seeded by `[prefix, agent]`.
+fn transfer_from_agent_pda<'info>(
+    prefix: &[u8],
+    agent_key: &Pubkey,
+    bump: u8,
+    from: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    amount: u64,
+) -> Result<()> {
+    let ix = anchor_lang::solana_program::system_instruction::transfer(
+        from.key,
+        to.key,
+        amount,
+    );
+    anchor_lang::solana_program::program::invoke_signed(
+        &ix,
+        &[
+            from.clone(),
+            to.clone(),
+            system_program.to_account_info(),
+        ],
+        &[&[prefix, agent_key.as_ref(), &[bump]]],
+    )?;
+    Ok(())
+}
+
+/// Everything the seller side requires before accepting a service payment.
+fn require_payable(
+    agent: &Agent,
+    amount: u64,
+    payer: &Pubkey,
+    policy: Option<&PaymentPolicy>,
+    price_feed: Option<&AccountInfo>,
+    now: i64,
+) -> Result<()> {
+    require!(agent.is_alive, BroodError::AgentDead);
+    require!(!agent.is_paused, BroodError::AgentPaused);
+    require!(!agent.frozen, BroodError::AgentFrozen);
+    require!(
+        amount >= service_price_lamports(agent, price_feed, now)?,
+        BroodError::BelowServicePrice
+    );
+    if agent.has_payment_policy {
+        let Some(policy) = policy else {
+            return err!(BroodError::PayerNotAllowed);
+        };
+        require!(policy.allows(payer), BroodError::PayerNotAllowed);
+    }
+    require!(
+        agent.service_count == 0
+            || now.saturating_sub(agent.last_service_at) >= agent.min_service_interval,
+        BroodError::ServiceRateLimited
+    );
+    Ok(())
+}
+
+/// Signal keepers once, on the payment that lifts the treasury past the
+/// agent's auto-spawn threshold.
+fn emit_if_spawn_eligible(agent: &Account<Agent>, treasury_before: u64, now: i64) {
+    if let Some(threshold) = agent.auto_spawn_threshold {
+        if treasury_before <= threshold && agent.treasury > threshold {
+            emit!(AutoSpawnEligible {
+                agent: agent.key(),
+                treasury: agent.treasury,
+                threshold,
+                timestamp: now,
+            });
+        }
+    }
+}
+
+/// Rent-exempt minimum for a treasury PDA, which holds no data.
+fn min_treasury_rent() -> Result<u64> {
+    Ok(Rent::get()?.minimum_balance(0))
+}
+
+/// The part of a treasury handed back by `retire` or `reap` that counts
+/// against the daily withdraw cap. The treasury's rent isn't spendable
+/// balance, and counting it would let a small cap keep an agent from ever
+/// being closed.
+fn teardown_payout(agent: &Agent, amount: u64) -> Result<u64> {
+    if agent.treasury_mint.is_some() {
+        return Ok(amount);
+    }
+    Ok(amount.saturating_sub(min_treasury_rent()?))
+}
+
+/// A parent must cover the seed and still keep its operating reserve.
+/// The two shortfalls get distinct errors so clients can say which one hit.
+fn require_spawn_funds(treasury: u64, seed: u64, reserve: u64) -> Result<()> {
+    let needed = seed.checked_add(reserve).ok_or(BroodError::AmountOverflow)?;
+    require!(treasury >= seed, BroodError::InsufficientTreasury);
+    require!(treasury >= needed, BroodError::BelowReserveAfterSpawn);
+    Ok(())
+}
+
+/// Keep a native treasury above the rent floor after an outbound transfer.
+/// Emptying it outright is only allowed where `allow_empty` says so.
+fn require_treasury_rent(remaining: u64, allow_empty: bool) -> Result<()> {
+    require!(
+        (allow_empty && remaining == 0) || remaining >= min_treasury_rent()?,
+        BroodError::WouldDeallocateTreasury
+    );
+    Ok(())
+}
+
+/// Grow a forced debit to the whole balance when it would otherwise leave
+/// less than rent behind, so charges that can't be refused still land.
+fn sweep_below_rent(balance: u64, debit: u64) -> Result<u64> {
+    if balance - debit < min_treasury_rent()? {
+        Ok(balance)
+    } else {
+        Ok(debit)
+    }
+}
+
+/// Owner of a child being spawned: the `child_owner` account when one is
+/// passed, otherwise the signer paying for it.
+fn child_owner_key(owner: &Signer, child_owner: &Option<Signer>) -> Pubkey {
+    child_owner.as_ref().map_or(owner.key(), |account| account.key())
+}
+
+/// Count a new agent, refusing it once `owner` already holds `limit`
+/// agents. A limit of zero means unlimited.
+fn count_birth(
+    population: &mut Population,
+    stats: &mut OwnerStats,
+    owner: Pubkey,
+    limit: u32,
+) -> Result<()> {
+    require!(limit == 0 || stats.live < limit, BroodError::OwnerAgentLimit);
+    stats.owner = owner;
+    stats.live = stats.live.checked_add(1).ok_or(BroodError::MathOverflow)?;
+    stats.total = stats.total.checked_add(1).ok_or(BroodError::MathOverflow)?;
+    population.live = population.live.checked_add(1).ok_or(BroodError::MathOverflow)?;
+    population.total = population.total.checked_add(1).ok_or(BroodError::MathOverflow)?;
+    Ok(())
+}
+
+/// Move an agent's count from one owner to another, refusing it once `to`
+/// already holds `limit` agents. A transfer is no birth, so `total` stays.
+fn count_transfer(from: &mut OwnerStats, to: &mut OwnerStats, owner: Pubkey, limit: u32) -> Result<()> {
+    require!(limit == 0 || to.live < limit, BroodError::OwnerAgentLimit);
+    from.live = from.live.saturating_sub(1);
+    to.owner = owner;
+    to.live = to.live.checked_add(1).ok_or(BroodError::MathOverflow)?;
+    Ok(())
+}
+
+/// Uncount a reaped agent. Saturating, so agents created before the
+/// counters existed can't drive them below zero.
+fn count_removal(population: &mut Population, stats: &mut OwnerStats) {
+    population.live = population.live.saturating_sub(1);
+    stats.live = stats.live.saturating_sub(1);
+}
+
+/// Flip an agent to dead and announce it.
+fn mark_dead(agent: &mut Agent, reason: DeathReason, now: i64) {
+    agent.is_alive = false;
+    agent.death_reason = Some(reason);
+    emit!(AgentDied {
+        agent: agent.id,
+        generation: agent.generation,
+        total_earnings: agent.total_earnings,
+        total_costs: agent.total_costs,
+        timestamp: now,
+        reason,
+    });
+}
+
+/// Seconds of decay owed at the current rate: since the agent's last
+/// charge or the last rate change, whichever is later.
+fn decay_elapsed(last_decay_at: i64, rate_set_at: i64, now: i64) -> u64 {
+    now.saturating_sub(last_decay_at.max(rate_set_at)).max(0) as u64
+}
+
+/// Move the treasury decay accrued since `last_decay_at` to the protocol.
+/// Decay is a cost like upkeep, but runs on its own clock so calling `tick`
+/// instead of `accrue_costs` doesn't dodge it. Time before `rate_set_at`
+/// is forgiven, so raising the rate never charges it retroactively.
+fn charge_decay<'info>(
+    agent: &mut Account<'info, Agent>,
+    treasury: &AccountInfo<'info>,
+    protocol_treasury: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    bps_per_day: u16,
+    rate_set_at: i64,
+    now: i64,
+) -> Result<()> {
+    require!(agent.is_alive, BroodError::AgentDead);
+    require_native_treasury(agent)?;
+
+    let elapsed = decay_elapsed(agent.last_decay_at, rate_set_at, now);
+    agent.last_decay_at = now;
+    let decay = agent.compute_decay(elapsed, bps_per_day);
+    if decay == 0 {
+        return Ok(());
+    }
+    let charged = sweep_below_rent(agent.treasury, decay)?;
+
+    transfer_from_treasury(
+        &agent.key(),
+        agent.treasury_bump,
+        treasury,
+        protocol_treasury,
+        system_program,
+        charged,
+    )?;
+    agent.treasury -= charged;
+    agent.total_costs = agent.total_costs.checked_add(charged).ok_or(BroodError::MathOverflow)?;
+    assert_treasury_consistency(agent, treasury)?;
+
+    emit!(TreasuryDecayed {
+        agent: agent.key(),
+        amount: charged,
+        treasury: agent.treasury,
+        timestamp: now,
+    });
+    Ok(())
+}
+
+/// Charge the upkeep accrued since `last_cost_at` into `sink`. An agent
+/// that can't cover it pays what it has, and dies once it has stayed
+/// empty for `grace_period` seconds. Leaves `last_active` alone: anyone
+/// can trigger this through `accrue_costs`, so it is no sign of life.
+fn charge_upkeep<'info>(
+    agent: &mut Account<'info, Agent>,
+    treasury: &AccountInfo<'info>,
+    sink: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    grace_period: i64,
+    now: i64,
+) -> Result<()> {
+    require!(agent.is_alive, BroodError::AgentDead);
+    require_native_treasury(agent)?;
+
+    let elapsed = now.saturating_sub(agent.last_cost_at).max(0) as u64;
+    let upkeep = agent.compute_upkeep(elapsed);
+    let charged = sweep_below_rent(agent.treasury, upkeep.min(agent.treasury))?;
+
+    if charged > 0 {
+        transfer_from_treasury(
+            &agent.key(),
+            agent.treasury_bump,
+            treasury,
+            sink,
+            system_program,
+            charged,
+        )?;
+    }
+
+    agent.treasury = agent.treasury.checked_sub(charged).ok_or(BroodError::MathOverflow)?;
+    agent.total_costs = agent.total_costs.checked_add(charged).ok_or(BroodError::MathOverflow)?;
+    agent.last_cost_at = now;
+    assert_treasury_consistency(agent, treasury)?;
+
+    emit!(CostsDeducted {
+        agent: agent.key(),
+        amount: charged,
+        cost_sink: sink.key(),
+        treasury: agent.treasury,
+        total_costs: agent.total_costs,
+        timestamp: now,
+    });
+
+    // Checked even when nothing was owed: a percent-of-treasury model
+    // charges an empty treasury nothing, which must not stop the clock
+    if agent.treasury == 0 {
+        if settle_depletion(agent, DeathReason::UpkeepUnpayable, grace_period, charged > 0, now) {
+            msg!("Agent {} has died (upkeep unpayable)", agent.name);
+        }
+    } else {
+        end_grace(agent, now);
+        if agent.below_performance_floor() {
+            mark_dead(agent, DeathReason::PerformanceFloor, now);
+            msg!("Agent {} has died (below performance floor)", agent.name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Handle an agent left at zero treasury. `newly_depleted` means this
+/// charge is what emptied it, which (re)starts the grace period; the agent
+/// dies only once it has been empty for `grace_period` seconds. Returns
+/// whether it died.
+fn settle_depletion(
+    agent: &mut Agent,
+    reason: DeathReason,
+    grace_period: i64,
+    newly_depleted: bool,
+    now: i64,
+) -> bool {
+    if newly_depleted || agent.depleted_since.is_none() {
+        agent.depleted_since = Some(now);
+        if grace_period > 0 {
+            emit!(GracePeriodStarted {
+                agent: agent.id,
+                ends_at: now.saturating_add(grace_period),
+                timestamp: now,
+            });
+            msg!("Agent {} treasury depleted, {}s to refund it", agent.name, grace_period);
+        }
+    }
+    let since = agent.depleted_since.unwrap_or(now);
+    if now.saturating_sub(since) < grace_period {
+        return false;
+    }
+    mark_dead(agent, reason, now);
+    true
+}
+
+/// End the grace period of an agent whose treasury is no longer empty.
+/// Every path that credits a treasury calls this, so a refund from any
+/// source, not just `fund_treasury`, saves the agent.
+fn end_grace(agent: &mut Agent, now: i64) {
+    if agent.treasury > 0 && agent.depleted_since.take().is_some() {
+        emit!(GracePeriodCancelled {
+            agent: agent.id,
+            treasury: agent.treasury,
+            timestamp: now,
+        });
+    }
+}
+
+/// Remove `child` from the lineage of `parent`, which must be the one passed.
+fn drop_from_lineage(
+    lineage: Option<&mut Account<Lineage>>,
+    parent: &Pubkey,
+    child: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<()> {
+    let Some(lineage) = lineage else {
+        return err!(BroodError::LineageMismatch);
+    };
+    let (expected, _) = Pubkey::find_program_address(&[b"lineage", parent.as_ref()], program_id);
+    require_keys_eq!(lineage.key(), expected, BroodError::LineageMismatch);
+    lineage.children.retain(|c| c != child);
+    Ok(())
+}
+
+/// Deserialize an agent passed as an unchecked account.
+fn load_agent(info: &AccountInfo) -> Result<Agent> {
+    require_keys_eq!(*info.owner, crate::ID, BroodError::NotAnAgent);
+    let data = info.try_borrow_data()?;
+    Agent::try_deserialize(&mut &data[..])
+}
+
+/// Write back an agent loaded with `load_agent`.
+fn store_agent(info: &AccountInfo, agent: &Agent) -> Result<()> {
+    let mut data = info.try_borrow_mut_data()?;
+    agent.try_serialize(&mut &mut data[..])
+}
+
+/// Move lamports from a signer into an agent's treasury PDA.
+fn deposit_lamports<'info>(
+    from: &Signer<'info>,
+    treasury: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    amount: u64,
+) -> Result<()> {
+    let ix = anchor_lang::solana_program::system_instruction::transfer(
+        from.key,
+        treasury.key,
+        amount,
+    );
+    anchor_lang::solana_program::program::invoke(
+        &ix,
+        &[
+            from.to_account_info(),
+            treasury.clone(),
+            system_program.to_account_info(),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Move tokens from a signer's token account into the treasury vault.
+fn deposit_tokens<'info>(
+    authority: &Signer<'info>,
+    source: &Account<'info, TokenAccount>,
+    vault: &Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+    amount: u64,
+) -> Result<()> {
+    token::transfer(
+        CpiContext::new(
+            token_program.to_account_info(),
+            token::Transfer {
+                from: source.to_account_info(),
+                to: vault.to_account_info(),
+                authority: authority.to_account_info(),
+            },
+        ),
+        amount,
+    )
+}
+
+/// Move tokens out of the treasury vault, signed by the treasury PDA.
+fn transfer_tokens_from_treasury<'info>(
+    agent_key: &Pubkey,
+    treasury_bump: u8,
+    treasury: &AccountInfo<'info>,
+    vault: &Account<'info, TokenAccount>,
+    destination: &Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+    amount: u64,
+) -> Result<()> {
+    token::transfer(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            token::Transfer {
+                from: vault.to_account_info(),
+                to: destination.to_account_info(),
+                authority: treasury.clone(),
+            },
+            &[&[b"treasury", agent_key.as_ref(), &[treasury_bump]]],
+        ),
+        amount,
+    )
+}
+
+/// A token treasury lives in the treasury PDA's associated token account
+/// for the agent's mint.
+fn is_treasury_vault(
+    agent: &Agent,
+    treasury: &AccountInfo,
+    vault: &Account<TokenAccount>,
+) -> bool {
+    match agent.treasury_mint {
+        Some(mint) => {
+            let (ata, _) = Pubkey::find_program_address(
+                &[treasury.key.as_ref(), token::ID.as_ref(), mint.as_ref()],
+                &ASSOCIATED_TOKEN_PROGRAM_ID,
+            );
+            vault.key() == ata
+        }
+        None => false,
+    }
+}
+
+/// `service_price` in lamports. USD prices are converted at the feed's
+/// current aggregate price, which must be trading and no older than
+/// `MAX_PRICE_AGE`.
+fn service_price_lamports(agent: &Agent, price_feed: Option<&AccountInfo>, now: i64) -> Result<u64> {
+    let PriceMode::UsdCents { feed } = agent.price_mode else {
+        return Ok(agent.service_price);
+    };
+    let Some(price_feed) = price_feed else {
+        return err!(BroodError::PriceFeedMismatch);
+    };
+    require_keys_eq!(price_feed.key(), feed, BroodError::PriceFeedMismatch);
+    let (price, expo) = read_pyth_price(price_feed, now)?;
+
+    // cents / 100 USD at price * 10^expo USD per SOL, times 1e9 lamports
+    require!((-18..=0).contains(&expo), BroodError::InvalidPriceFeed);
+    let numerator = (agent.service_price as u128)
+        .checked_mul(10_000_000 * 10u128.pow(expo.unsigned_abs()))
+        .ok_or(BroodError::AmountOverflow)?;
+    let lamports = numerator.div_ceil(price as u128);
+    u64::try_from(lamports).map_err(|_| error!(BroodError::AmountOverflow))
+}
+
+/// Aggregate price and exponent from a Pyth v2 price account, read by
+/// offset since the program doesn't link the Pyth SDK.
+fn read_pyth_price(feed: &AccountInfo, now: i64) -> Result<(u64, i32)> {
+    const MAGIC: u32 = 0xa1b2_c3d4;
+    const PRICE_ACCOUNT: u32 = 3;
+    const STATUS_TRADING: u32 = 1;
+
+    let data = feed.try_borrow_data()?;
+    require!(data.len() >= 240, BroodError::InvalidPriceFeed);
+    let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+    let i64_at = |at: usize| i64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+
+    require!(
+        u32_at(0) == MAGIC && u32_at(4) == 2 && u32_at(8) == PRICE_ACCOUNT,
+        BroodError::InvalidPriceFeed
+    );
+    let expo = u32_at(20) as i32;
+    let published_at = i64_at(96);
+    let price = i64_at(208);
+    let status = u32_at(224);
+
+    require!(status == STATUS_TRADING, BroodError::StalePriceFeed);
+    require!(now.saturating_sub(published_at) <= MAX_PRICE_AGE, BroodError::StalePriceFeed);
+    require!(price > 0, BroodError::InvalidPriceFeed);
+    Ok((price as u64, expo))
+}
+
+/// Log `label` and the remaining compute budget when the config asks for it.
+/// Builds without the `debug-compute` feature skip even the flag check.
+#[cfg(feature = "debug-compute")]
+fn log_compute(config: &ProtocolConfig, label: &str) {
+    if config.debug_compute {
+        msg!(label);
+        anchor_lang::solana_program::log::sol_log_compute_units();
+    }
+}
+
+#[cfg(not(feature = "debug-compute"))]
+fn log_compute(_config: &ProtocolConfig, _label: &str) {}
+
+/// Instructions that move lamports between treasuries don't handle tokens.
+fn require_native_treasury(agent: &Agent) -> Result<()> {
+    require!(agent.treasury_mint.is_none(), BroodError::NativeTreasuryOnly);
+    Ok(())
+}
+
+/// Names are PDA seeds and show up in UIs, so keep them printable: no
+/// control characters, no surrounding whitespace, and not empty.
+fn validate_name(name: &str) -> Result<()> {
+    require!(name.len() <= MAX_NAME_LEN, BroodError::NameTooLong);
+    require!(!name.is_empty(), BroodError::InvalidName);
+    require!(name.trim() == name, BroodError::InvalidName);
+    require!(!name.chars().any(char::is_control), BroodError::InvalidName);
+    Ok(())
+}
+
+fn validate_metadata_uri(uri: &str) -> Result<()> {
+    require!(uri.len() <= MAX_METADATA_URI_LEN, BroodError::UriTooLong);
+    require!(
+        uri.starts_with("https://") || uri.starts_with("ipfs://"),
+        BroodError::InvalidMetadataUri
+    );
+    Ok(())
+}
+
+/// Per-call pseudo-random rank for breaking fitness ties in `cull`.
+/// Mixing in caller entropy keeps a ground address from always winning.
+fn cull_tiebreak(agent: &Pubkey, entropy: &[u8; 32]) -> [u8; 32] {
+    anchor_lang::solana_program::keccak::hashv(&[agent.as_ref(), entropy]).to_bytes()
+}
+
+/// Commitment scheme used by `commit_genome` / `reveal_genome`.
+fn genome_commitment(genome_hash: &[u8; 32], salt: &[u8; 32]) -> [u8; 32] {
+    anchor_lang::solana_program::keccak::hashv(&[genome_hash, salt]).to_bytes()
+}
+
+/// A child PDA that already holds an agent, live or dead, keeps its name
+/// until that agent is reaped. `init_if_needed` on the child lets us
+/// report this as `NameTaken` instead of a generic account-in-use error.
+fn require_unused_name(child: &Agent) -> Result<()> {
+    require_keys_eq!(child.id, Pubkey::default(), BroodError::NameTaken);
+    Ok(())
+}
+
+fn require_spawn_cooldown_elapsed(agent: &Agent, now: i64) -> Result<()> {
+    let elapsed = now.saturating_sub(agent.last_spawn_at);
+    require!(elapsed >= agent.spawn_cooldown, BroodError::SpawnCooldownActive);
+    Ok(())
+}
+
+/// The treasury PDA holds no data, so every lamport in it is spendable balance.
+/// It may never hold less than the accounting field. Anyone can send lamports
+/// straight to the PDA, so a surplus is folded into the balance rather than
+/// refused; otherwise a one-lamport transfer would wedge the agent for good.
+fn assert_treasury_consistency(agent: &mut Agent, treasury: &AccountInfo) -> Result<()> {
+    let lamports = treasury.lamports();
+    require_gte!(lamports, agent.treasury, BroodError::TreasuryMismatch);
+    agent.treasury = lamports;
+    Ok(())
+}
+
+/// Token counterpart of `assert_treasury_consistency`; reloads the vault
+/// so balances changed by a CPI are visible.
+fn assert_token_treasury_consistency(
+    agent: &mut Agent,
+    vault: &mut Account<TokenAccount>,
+) -> Result<()> {
+    vault.reload()?;
+    require_gte!(vault.amount, agent.treasury, BroodError::TreasuryMismatch);
+    agent.treasury = vault.amount;
+    Ok(())
 }
 
 // ============================================================================
@@ -325,4 +6541,1145 @@ pub enum BroodError {
     NameTooLong,
     #[msg("URI too long (max 128 chars)")]
     UriTooLong,
+    #[msg("Withdrawal would drop a live agent below its operating reserve")]
+    WithdrawalBelowReserve,
+    #[msg("Treasury accounting does not match the treasury PDA balance")]
+    TreasuryMismatch,
+    #[msg("Agent is still alive")]
+    AgentStillAlive,
+    #[msg("Both parents must be distinct agents")]
+    DuplicateParent,
+    #[msg("Token account does not match the agent's treasury mint")]
+    TreasuryMintMismatch,
+    #[msg("Token treasury requires the vault, counterparty token account and token program")]
+    MissingTokenAccounts,
+    #[msg("Instruction only supports native SOL treasuries")]
+    NativeTreasuryOnly,
+    #[msg("Treasury must be emptied first")]
+    TreasuryNotEmpty,
+    #[msg("Name is already registered to another agent")]
+    NameTaken,
+    #[msg("Name registry must be passed exactly when global_name is set")]
+    NameRegistryMismatch,
+    #[msg("Agent is paused")]
+    AgentPaused,
+    #[msg("Parent has a grandparent; its agent and treasury accounts are required")]
+    MissingGrandparent,
+    #[msg("Grandparent accounts do not match the parent's lineage")]
+    GrandparentMismatch,
+    #[msg("Account is not a Brood agent")]
+    NotAnAgent,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("Signer is neither the owner nor the operator")]
+    UnauthorizedOperator,
+    #[msg("Lineage has reached its maximum generation")]
+    MaxGenerationExceeded,
+    #[msg("Agent spawned too recently")]
+    SpawnCooldownActive,
+    #[msg("Agent has reached its maximum number of children")]
+    ChildLimitReached,
+    #[msg("Cannot merge an agent into itself")]
+    MergeIntoSelf,
+    #[msg("Fee cannot exceed 10000 basis points")]
+    InvalidFeeBps,
+    #[msg("Protocol treasury does not match the config")]
+    ProtocolTreasuryMismatch,
+    #[msg("Service amount is too small to cover the protocol fee")]
+    ServiceAmountTooSmall,
+    #[msg("Agent has no genome commitment to reveal")]
+    NoGenomeCommitment,
+    #[msg("Genome and salt do not match the commitment")]
+    CommitmentMismatch,
+    #[msg("Payment is below the agent's service price")]
+    BelowServicePrice,
+    #[msg("Cohort accounts are malformed")]
+    InvalidCohort,
+    #[msg("Cohort is not sorted by fitness, best first")]
+    CohortNotSorted,
+    #[msg("Name is empty, padded with whitespace, or has control characters")]
+    InvalidName,
+    #[msg("Adoption would create or could not rule out a lineage cycle")]
+    AdoptionCycle,
+    #[msg("Lineage account does not belong to this parent")]
+    LineageMismatch,
+    #[msg("Signer does not own this agent")]
+    Unauthorized,
+    #[msg("Basis points cannot exceed 10000")]
+    InvalidBps,
+    #[msg("Metadata URI must start with https:// or ipfs://")]
+    InvalidMetadataUri,
+    #[msg("Service payments are arriving faster than the agent allows")]
+    ServiceRateLimited,
+    #[msg("Payer is not on this agent's allowlist")]
+    PayerNotAllowed,
+    #[msg("Allowlist is full")]
+    AllowlistFull,
+    #[msg("Unstake the treasury first")]
+    TreasuryStaked,
+    #[msg("An agent cannot play itself")]
+    SelfMatch,
+    #[msg("Winner must be one of the two agents")]
+    InvalidWinner,
+    #[msg("Agent has been eliminated from this tournament")]
+    AgentEliminated,
+    #[msg("Account is not a child of this agent")]
+    NotAChild,
+    #[msg("Agent is too young to reproduce")]
+    AgentTooYoung,
+    #[msg("Agent has not served enough customers or turned a profit")]
+    AgentUnproven,
+    #[msg("Treasury balance would overflow")]
+    TreasuryOverflow,
+    #[msg("Amount must be greater than zero")]
+    ZeroAmount,
+    #[msg("Agent is frozen by the protocol admin")]
+    AgentFrozen,
+    #[msg("Withdrawal would exceed the agent's daily limit")]
+    WithdrawLimitExceeded,
+    #[msg("An agent cannot pay itself")]
+    SelfPayment,
+    #[msg("Transfer would leave the treasury below rent exemption")]
+    WouldDeallocateTreasury,
+    #[msg("Owner already holds the maximum number of agents")]
+    OwnerAgentLimit,
+    #[msg("Agent still has spawn funds in escrow")]
+    EscrowPending,
+    #[msg("Agent has no escrow to release")]
+    NoEscrow,
+    #[msg("Probation period has not ended")]
+    ProbationActive,
+    #[msg("Probation period cannot be negative")]
+    InvalidProbation,
+    #[msg("Subscription interval must be positive")]
+    InvalidInterval,
+    #[msg("Subscription already paid this interval")]
+    SubscriptionNotDue,
+    #[msg("Subscription vault is empty")]
+    SubscriptionEmpty,
+    #[msg("Seed plus operating reserve overflows")]
+    AmountOverflow,
+    #[msg("Spawn would drop the parent below its operating reserve")]
+    BelowReserveAfterSpawn,
+    #[msg("Payment would lift the treasury past its cap")]
+    TreasuryCapExceeded,
+    #[msg("Accounts must be passed in ascending key order")]
+    UnsortedCohort,
+    #[msg("Agent belongs to a different bloodline")]
+    NotInBrood,
+    #[msg("Program is paused")]
+    ProgramPaused,
+    #[msg("Cannot transfer a treasury to itself")]
+    TransferToSelf,
+    #[msg("Agent has been active within the inactivity timeout")]
+    AgentStillActive,
+    #[msg("Price feed is stale or not trading")]
+    StalePriceFeed,
+    #[msg("Price feed missing or not the one the agent prices against")]
+    PriceFeedMismatch,
+    #[msg("Account is not a usable Pyth price feed")]
+    InvalidPriceFeed,
+    #[msg("Agent's genome is sealed")]
+    GenomeSealed,
+    #[msg("Shares must match the recipient accounts and sum to 10000 bps")]
+    InvalidDistribution,
+    #[msg("Grace period cannot be negative")]
+    InvalidGracePeriod,
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    /// A freshly initialised gen-1 agent with an empty treasury.
+    fn agent() -> Agent {
+        let mut agent = unused_agent();
+        agent.init(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            "agent".to_string(),
+            [0; 32],
+            String::new(),
+            NOW,
+        );
+        agent
+    }
+
+    /// The state `init_if_needed` leaves a brand-new or reaped account in.
+    fn unused_agent() -> Agent {
+        let zeroed = vec![0u8; Agent::INIT_SPACE];
+        Agent::deserialize(&mut zeroed.as_slice()).unwrap()
+    }
+
+    /// Protocol config carrying the default fee and spawn seed settings.
+    fn config() -> ProtocolConfig {
+        let zeroed = vec![0u8; ProtocolConfig::INIT_SPACE];
+        let mut config = ProtocolConfig::deserialize(&mut zeroed.as_slice()).unwrap();
+        config.protocol_fee_bps = PROTOCOL_FEE_BPS;
+        config.min_spawn_seed = MIN_SPAWN_SEED;
+        config.seed_growth_bps = SEED_GROWTH_BPS;
+        config
+    }
+
+    fn assert_err<T: std::fmt::Debug>(result: Result<T>, expected: BroodError) {
+        assert_eq!(result.unwrap_err(), expected.into());
+    }
+
+    fn default_weights() -> FitnessWeights {
+        FitnessWeights {
+            earnings_weight_bps: EARNINGS_WEIGHT_BPS,
+            score_weight: SCORE_WEIGHT,
+            generation_penalty: GENERATION_PENALTY_BASE,
+        }
+    }
+
+    #[test]
+    fn fitness_holds_earnings_at_u64_max() {
+        let mut agent = agent();
+        agent.total_earnings = u64::MAX;
+        let weights = FitnessWeights { generation_penalty: 0, ..default_weights() };
+        assert_eq!(agent.fitness(&weights), u64::MAX as i128);
+
+        agent.total_costs = u64::MAX;
+        assert_eq!(agent.fitness(&weights), 0);
+
+        agent.total_earnings = 0;
+        assert_eq!(agent.fitness(&weights), -(u64::MAX as i128));
+    }
+
+    #[test]
+    fn fitness_discounts_deeper_generations() {
+        let mut agent = agent();
+        agent.total_earnings = u64::MAX;
+        let shallow = agent.fitness(&default_weights());
+        agent.generation = u32::MAX;
+        let deep = agent.fitness(&default_weights());
+        assert!(deep > 0 && deep < shallow);
+    }
+
+    #[test]
+    fn record_service_rejects_earnings_overflow() {
+        let mut agent = agent();
+        let half = u64::MAX / 2 + 1;
+        agent.record_service(half, &default_weights(), NOW).unwrap();
+        assert_err(
+            agent.record_service(half, &default_weights(), NOW),
+            BroodError::MathOverflow,
+        );
+        assert_eq!(agent.total_earnings, half);
+        assert_eq!(agent.service_count, 1);
+    }
+
+    #[test]
+    fn min_seed_grows_with_generation() {
+        let config = config();
+        assert_eq!(config.min_seed_for_generation(1), MIN_SPAWN_SEED);
+        // Nine generations past the first at 10% each
+        assert_eq!(config.min_seed_for_generation(10), MIN_SPAWN_SEED * 19 / 10);
+    }
+
+    #[test]
+    fn min_seed_saturates_instead_of_overflowing() {
+        let mut config = config();
+        config.min_spawn_seed = u64::MAX / 2;
+        config.seed_growth_bps = u16::MAX;
+        assert_eq!(config.min_seed_for_generation(u32::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn win_rate_counts_only_played_matches() {
+        let mut agent = agent();
+        assert_eq!(agent.win_rate_bps(), 0);
+        agent.wins = 3;
+        agent.losses = 1;
+        assert_eq!(agent.win_rate_bps(), 7_500);
+        agent.wins = u32::MAX;
+        agent.losses = 0;
+        assert_eq!(agent.win_rate_bps(), 10_000);
+    }
+
+    #[test]
+    fn validate_name_allows_emoji_within_budget() {
+        validate_name("crab 🦀").unwrap();
+        // Eight four-byte emoji fill the 32-byte budget exactly
+        validate_name(&"🦀".repeat(8)).unwrap();
+    }
+
+    #[test]
+    fn validate_name_rejects_bad_names() {
+        assert_err(validate_name(&"a".repeat(33)), BroodError::NameTooLong);
+        assert_err(validate_name(&"🦀".repeat(9)), BroodError::NameTooLong);
+        assert_err(validate_name(""), BroodError::InvalidName);
+        assert_err(validate_name(" padded"), BroodError::InvalidName);
+        assert_err(validate_name("padded "), BroodError::InvalidName);
+        assert_err(validate_name("nul\0byte"), BroodError::InvalidName);
+        assert_err(validate_name("tab\tname"), BroodError::InvalidName);
+    }
+
+    #[test]
+    fn mark_dead_records_each_reason() {
+        for reason in [
+            DeathReason::TreasuryDepleted,
+            DeathReason::PerformanceFloor,
+            DeathReason::Euthanized,
+            DeathReason::UpkeepUnpayable,
+            DeathReason::Culled,
+            DeathReason::Merged,
+            DeathReason::Inactive,
+        ] {
+            let mut agent = agent();
+            mark_dead(&mut agent, reason, NOW);
+            assert!(!agent.is_alive);
+            assert_eq!(agent.death_reason, Some(reason));
+        }
+    }
+
+    #[test]
+    fn performance_floor_trips_below_min_performance() {
+        let mut agent = agent();
+        assert!(!agent.below_performance_floor());
+        agent.min_performance = Some(0);
+        agent.total_costs = 1;
+        assert!(agent.below_performance_floor());
+        agent.total_earnings = 1;
+        assert!(!agent.below_performance_floor());
+    }
+
+    #[test]
+    fn child_name_is_taken_while_an_agent_holds_it() {
+        let mut existing = agent();
+        assert_err(require_unused_name(&existing), BroodError::NameTaken);
+        // Dead agents keep their name until reaped
+        mark_dead(&mut existing, DeathReason::Euthanized, NOW);
+        assert_err(require_unused_name(&existing), BroodError::NameTaken);
+    }
+
+    #[test]
+    fn child_name_is_free_once_reaped() {
+        require_unused_name(&unused_agent()).unwrap();
+    }
+
+    #[test]
+    fn flat_upkeep_scales_with_elapsed_time() {
+        let mut agent = agent();
+        for elapsed in [0, 1, 60, 3_600, 86_400] {
+            assert_eq!(agent.compute_upkeep(elapsed), elapsed * UPKEEP_PER_SECOND);
+        }
+        // Each revive adds another base rate
+        agent.revive_count = 2;
+        assert_eq!(agent.compute_upkeep(86_400), 86_400 * UPKEEP_PER_SECOND * 3);
+        assert_eq!(agent.compute_upkeep(u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn funding_rejects_zero() {
+        assert_err(agent().require_fundable(0), BroodError::ZeroAmount);
+        agent().require_fundable(1).unwrap();
+    }
+
+    #[test]
+    fn funding_rejects_treasury_overflow() {
+        let mut agent = agent();
+        agent.treasury = u64::MAX - 1;
+        agent.credit_treasury(1).unwrap();
+        assert_eq!(agent.treasury, u64::MAX);
+        assert_err(agent.credit_treasury(1), BroodError::TreasuryOverflow);
+        assert_eq!(agent.treasury, u64::MAX);
+    }
+
+    #[test]
+    fn withdraw_limit_resets_daily() {
+        let mut agent = agent();
+        agent.withdraw_limit_per_day = 100;
+        agent.record_withdrawal(60, NOW).unwrap();
+        assert_err(agent.record_withdrawal(41, NOW + 1), BroodError::WithdrawLimitExceeded);
+        agent.record_withdrawal(40, NOW + 1).unwrap();
+        agent.record_withdrawal(100, NOW + WITHDRAW_WINDOW).unwrap();
+    }
+
+    #[test]
+    fn raised_withdraw_limit_waits_out_its_delay() {
+        let mut agent = agent();
+        agent.withdraw_limit_per_day = 100;
+        agent.pending_withdraw_limit = Some(1_000);
+        agent.pending_withdraw_limit_at = NOW + WITHDRAW_LIMIT_DELAY;
+        assert_err(agent.record_withdrawal(500, NOW), BroodError::WithdrawLimitExceeded);
+        agent.record_withdrawal(500, NOW + WITHDRAW_LIMIT_DELAY).unwrap();
+        assert_eq!(agent.withdraw_limit_per_day, 1_000);
+        assert_eq!(agent.pending_withdraw_limit, None);
+    }
+
+    #[test]
+    fn treasury_percent_upkeep_charges_a_daily_share() {
+        let mut agent = agent();
+        agent.treasury = 1_000_000_000;
+        agent.cost_model = CostModel::TreasuryPercent { bps_per_day: 100 };
+        assert_eq!(agent.compute_upkeep(86_400), 10_000_000);
+        assert_eq!(agent.compute_upkeep(43_200), 5_000_000);
+        assert_eq!(agent.compute_upkeep(0), 0);
+        agent.treasury = 0;
+        assert_eq!(agent.compute_upkeep(86_400), 0);
+    }
+
+    #[test]
+    fn cost_models_price_the_same_interval_differently() {
+        let mut agent = agent();
+        agent.treasury = 1_000_000_000;
+        assert_eq!(agent.compute_upkeep(3_600), 3_600 * UPKEEP_PER_SECOND);
+        agent.cost_model = CostModel::TreasuryPercent { bps_per_day: 2_400 };
+        // 24% a day is 1% an hour
+        assert_eq!(agent.compute_upkeep(3_600), 10_000_000);
+        assert_err(
+            CostModel::TreasuryPercent { bps_per_day: 10_001 }.validate(),
+            BroodError::InvalidBps,
+        );
+    }
+
+    #[test]
+    fn decay_is_pro_rata_and_capped_at_the_treasury() {
+        let mut a = agent();
+        a.treasury = 10_000_000;
+        // 100 bps a day for a full day takes 1%
+        assert_eq!(a.compute_decay(86_400, 100), 100_000);
+        assert_eq!(a.compute_decay(43_200, 100), 50_000);
+        assert_eq!(a.compute_decay(0, 100), 0);
+        assert_eq!(a.compute_decay(86_400, 0), 0);
+        // Never more than the treasury holds
+        assert_eq!(a.compute_decay(86_400 * 365, 10_000), 10_000_000);
+    }
+
+    #[test]
+    fn decay_is_not_charged_retroactively_after_a_rate_change() {
+        // Charged at NOW, rate unchanged since before then
+        assert_eq!(decay_elapsed(NOW, NOW - 1_000, NOW + 86_400), 86_400);
+        // Rate raised half a day after the last charge: only the later half counts
+        assert_eq!(decay_elapsed(NOW, NOW + 43_200, NOW + 86_400), 43_200);
+        // A clock behind the checkpoint owes nothing
+        assert_eq!(decay_elapsed(NOW, NOW, NOW - 10), 0);
+    }
+
+    #[test]
+    fn cull_tiebreak_is_deterministic_but_reshuffled_by_entropy() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (e1, e2) = ([1u8; 32], [2u8; 32]);
+        assert_eq!(cull_tiebreak(&a, &e1), cull_tiebreak(&a, &e1));
+        assert_ne!(cull_tiebreak(&a, &e1), cull_tiebreak(&b, &e1));
+        assert_ne!(cull_tiebreak(&a, &e1), cull_tiebreak(&a, &e2));
+
+        // Tied agents each win about half the time across varied entropy
+        const TRIALS: u32 = 4_000;
+        let a_wins = (0..TRIALS)
+            .filter(|i| {
+                let mut entropy = [0u8; 32];
+                entropy[..4].copy_from_slice(&i.to_le_bytes());
+                cull_tiebreak(&a, &entropy) < cull_tiebreak(&b, &entropy)
+            })
+            .count() as f64;
+        let share = a_wins / TRIALS as f64;
+        assert!((0.4..0.6).contains(&share), "a won {share} of ties");
+    }
+
+    #[test]
+    fn service_fee_split_rounds_the_fee_down() {
+        let mut cfg = config();
+        cfg.protocol_fee_bps = 250;
+        assert_eq!(cfg.split_service_fee(1_000_000).unwrap(), (25_000, 975_000));
+        // 2.5% of 39 lamports rounds to nothing; the agent keeps it all
+        assert_eq!(cfg.split_service_fee(39).unwrap(), (0, 39));
+        assert_eq!(cfg.split_service_fee(u64::MAX).unwrap().0, (u64::MAX as u128 * 250 / 10_000) as u64);
+    }
+
+    #[test]
+    fn service_fee_split_rejects_payments_with_nothing_left_for_the_agent() {
+        let mut cfg = config();
+        assert_err(cfg.split_service_fee(0), BroodError::ServiceAmountTooSmall);
+        cfg.protocol_fee_bps = 10_000;
+        assert_err(cfg.split_service_fee(1_000), BroodError::ServiceAmountTooSmall);
+    }
+
+    /// Bytes of a Pyth v2 price account, laid out at the offsets
+    /// `read_pyth_price` reads.
+    fn pyth_feed(price: i64, expo: i32, status: u32, published_at: i64) -> Vec<u8> {
+        let mut data = vec![0u8; 240];
+        data[0..4].copy_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+        data[4..8].copy_from_slice(&2u32.to_le_bytes());
+        data[8..12].copy_from_slice(&3u32.to_le_bytes());
+        data[20..24].copy_from_slice(&expo.to_le_bytes());
+        data[96..104].copy_from_slice(&published_at.to_le_bytes());
+        data[208..216].copy_from_slice(&price.to_le_bytes());
+        data[224..228].copy_from_slice(&status.to_le_bytes());
+        data
+    }
+
+    fn read_feed(mut data: Vec<u8>, now: i64) -> Result<(u64, i32)> {
+        let (key, owner, mut lamports) = (Pubkey::new_unique(), Pubkey::new_unique(), 0);
+        let feed = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        read_pyth_price(&feed, now)
+    }
+
+    #[test]
+    fn pyth_price_reads_a_fresh_trading_feed() {
+        // $150.00000000 per SOL
+        assert_eq!(read_feed(pyth_feed(15_000_000_000, -8, 1, NOW), NOW).unwrap(), (15_000_000_000, -8));
+        assert!(read_feed(pyth_feed(1, -8, 1, NOW - MAX_PRICE_AGE), NOW).is_ok());
+    }
+
+    #[test]
+    fn pyth_price_rejects_stale_halted_and_malformed_feeds() {
+        assert_err(read_feed(pyth_feed(1, -8, 1, NOW - MAX_PRICE_AGE - 1), NOW), BroodError::StalePriceFeed);
+        assert_err(read_feed(pyth_feed(1, -8, 0, NOW), NOW), BroodError::StalePriceFeed);
+        assert_err(read_feed(pyth_feed(0, -8, 1, NOW), NOW), BroodError::InvalidPriceFeed);
+        assert_err(read_feed(pyth_feed(-5, -8, 1, NOW), NOW), BroodError::InvalidPriceFeed);
+
+        let mut wrong_magic = pyth_feed(1, -8, 1, NOW);
+        wrong_magic[0] ^= 0xff;
+        assert_err(read_feed(wrong_magic, NOW), BroodError::InvalidPriceFeed);
+        let mut short = pyth_feed(1, -8, 1, NOW);
+        short.truncate(239);
+        assert_err(read_feed(short, NOW), BroodError::InvalidPriceFeed);
+    }
+
+    #[test]
+    fn usd_price_converts_to_lamports_rounding_up() {
+        let (key, owner, mut lamports) = (Pubkey::new_unique(), Pubkey::new_unique(), 0);
+        let mut data = pyth_feed(15_000_000_000, -8, 1, NOW);
+        let feed = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+
+        let mut a = agent();
+        a.service_price = 150; // $1.50 at $150/SOL is 0.01 SOL
+        // Lamport-priced agents ignore the feed
+        assert_eq!(service_price_lamports(&a, Some(&feed), NOW).unwrap(), 150);
+        a.price_mode = PriceMode::UsdCents { feed: key };
+        assert_eq!(service_price_lamports(&a, Some(&feed), NOW).unwrap(), 10_000_000);
+        a.service_price = 1; // 1/15000 SOL isn't a whole lamport
+        assert_eq!(service_price_lamports(&a, Some(&feed), NOW).unwrap(), 66_667);
+
+        assert_err(service_price_lamports(&a, None, NOW), BroodError::PriceFeedMismatch);
+        a.price_mode = PriceMode::UsdCents { feed: Pubkey::new_unique() };
+        assert_err(service_price_lamports(&a, Some(&feed), NOW), BroodError::PriceFeedMismatch);
+    }
+
+    #[test]
+    fn inherited_record_scales_wins_and_losses_alike() {
+        let mut parent = agent();
+        parent.wins = 40;
+        parent.losses = 10;
+        assert_eq!(parent.inherited_record(5_000, false), (20, 5));
+        assert_eq!(parent.inherited_record(10_000, false), (40, 10));
+        assert_eq!(parent.inherited_record(0, false), (0, 0));
+        // Rounds down: 25% of 10 losses is 2
+        assert_eq!(parent.inherited_record(2_500, false), (10, 2));
+        // A winning parent is unaffected by the floor
+        assert_eq!(parent.inherited_record(5_000, true), (20, 5));
+    }
+
+    #[test]
+    fn inherited_record_passes_on_debt_unless_floored() {
+        let mut parent = agent();
+        parent.wins = 10;
+        parent.losses = 30;
+        assert_eq!(parent.inherited_record(5_000, false), (5, 15));
+        assert_eq!(parent.inherited_record(5_000, true), (5, 5));
+
+        parent.wins = u32::MAX;
+        parent.losses = u32::MAX;
+        assert_eq!(parent.inherited_record(10_000, false), (u32::MAX, u32::MAX));
+    }
+
+    #[test]
+    fn depleted_agent_dies_once_the_grace_period_runs_out() {
+        let mut a = agent();
+        assert!(!settle_depletion(&mut a, DeathReason::UpkeepUnpayable, 100, true, NOW));
+        assert_eq!(a.depleted_since, Some(NOW));
+        // A later charge that finds it still empty doesn't restart the clock
+        assert!(!settle_depletion(&mut a, DeathReason::UpkeepUnpayable, 100, false, NOW + 99));
+        assert_eq!(a.depleted_since, Some(NOW));
+        assert!(a.is_alive);
+
+        assert!(settle_depletion(&mut a, DeathReason::UpkeepUnpayable, 100, false, NOW + 100));
+        assert!(!a.is_alive);
+        assert_eq!(a.death_reason, Some(DeathReason::UpkeepUnpayable));
+    }
+
+    #[test]
+    fn zero_grace_period_kills_on_depletion() {
+        let mut a = agent();
+        assert!(settle_depletion(&mut a, DeathReason::TreasuryDepleted, 0, true, NOW));
+        assert_eq!(a.death_reason, Some(DeathReason::TreasuryDepleted));
+    }
+
+    #[test]
+    fn funding_during_grace_saves_the_agent() {
+        let mut a = agent();
+        assert!(!settle_depletion(&mut a, DeathReason::UpkeepUnpayable, 100, true, NOW));
+
+        // An empty treasury keeps the clock running
+        end_grace(&mut a, NOW + 10);
+        assert_eq!(a.depleted_since, Some(NOW));
+
+        a.treasury = 1;
+        end_grace(&mut a, NOW + 50);
+        assert_eq!(a.depleted_since, None);
+
+        // Emptied again well past the first deadline, it gets a fresh period
+        a.treasury = 0;
+        assert!(!settle_depletion(&mut a, DeathReason::UpkeepUnpayable, 100, true, NOW + 150));
+        assert_eq!(a.depleted_since, Some(NOW + 150));
+        assert!(a.is_alive);
+    }
+
+    // Just enough of the runtime to run instructions end to end: system
+    // transfers and account creation move real lamports, PDA signatures
+    // are checked against the calling program, and CPIs into brood are
+    // dispatched to `entry`. Accounts are leaked so instructions can hold
+    // them for `'static`.
+
+    use anchor_lang::solana_program::{
+        entrypoint::{ProgramResult, SUCCESS},
+        instruction::Instruction,
+        program_error::ProgramError,
+        program_stubs::{set_syscall_stubs, SyscallStubs},
+        program_utils::limited_deserialize,
+        system_instruction::SystemInstruction,
+        system_program,
+    };
+    use anchor_lang::{InstructionData, ToAccountMetas};
+    use std::cell::RefCell;
+
+    thread_local! {
+        /// Programs currently executing, innermost last
+        static CALLERS: RefCell<Vec<Pubkey>> = const { RefCell::new(Vec::new()) };
+    }
+
+    struct TestRuntime;
+
+    impl SyscallStubs for TestRuntime {
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = Clock { unix_timestamp: NOW, ..Clock::default() };
+            unsafe { *(var_addr as *mut Clock) = clock };
+            SUCCESS
+        }
+
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { *(var_addr as *mut Rent) = Rent::default() };
+            SUCCESS
+        }
+
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            let caller = CALLERS.with(|callers| *callers.borrow().last().unwrap());
+            let signed_pdas = signers_seeds
+                .iter()
+                .map(|seeds| Pubkey::create_program_address(seeds, &caller))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+
+            let mut accounts = Vec::new();
+            for meta in &instruction.accounts {
+                let mut info = account_infos
+                    .iter()
+                    .find(|info| *info.key == meta.pubkey)
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?
+                    .clone();
+                if meta.is_signer && !info.is_signer && !signed_pdas.contains(info.key) {
+                    return Err(ProgramError::MissingRequiredSignature);
+                }
+                if meta.is_writable && !info.is_writable {
+                    return Err(ProgramError::InvalidArgument);
+                }
+                info.is_signer = meta.is_signer;
+                info.is_writable = meta.is_writable;
+                accounts.push(info);
+            }
+
+            if instruction.program_id == system_program::ID {
+                run_system(&accounts, &instruction.data)
+            } else if instruction.program_id == crate::ID {
+                run_brood(&accounts, &instruction.data)
+            } else {
+                Err(ProgramError::IncorrectProgramId)
+            }
+        }
+    }
+
+    /// Run `f` as `program`, so the PDAs it signs for are derived from it.
+    fn as_program<T>(program: Pubkey, f: impl FnOnce() -> T) -> T {
+        CALLERS.with(|callers| callers.borrow_mut().push(program));
+        let result = f();
+        CALLERS.with(|callers| callers.borrow_mut().pop());
+        result
+    }
+
+    fn run_brood(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+        let accounts: &[AccountInfo] = Box::leak(accounts.to_vec().into_boxed_slice());
+        as_program(crate::ID, || crate::entry(&crate::ID, accounts, data))
+    }
+
+    /// The system instructions brood uses, with the checks the system
+    /// program makes on them.
+    fn run_system(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+        let system_owned = |info: &AccountInfo| -> ProgramResult {
+            if !info.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if *info.owner != system_program::ID || !info.data_is_empty() {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            Ok(())
+        };
+        let transfer = |from: &AccountInfo, to: &AccountInfo, lamports: u64| -> ProgramResult {
+            system_owned(from)?;
+            let debited = from.lamports().checked_sub(lamports).ok_or(ProgramError::InsufficientFunds)?;
+            let credited = to.lamports().checked_add(lamports).ok_or(ProgramError::ArithmeticOverflow)?;
+            **from.try_borrow_mut_lamports()? = debited;
+            **to.try_borrow_mut_lamports()? = credited;
+            Ok(())
+        };
+        let allocate = |info: &AccountInfo, space: u64| -> ProgramResult {
+            system_owned(info)?;
+            *info.try_borrow_mut_data()? = Box::leak(vec![0; space as usize].into_boxed_slice());
+            Ok(())
+        };
+
+        match limited_deserialize(data, 1_024).map_err(|_| ProgramError::InvalidInstructionData)? {
+            SystemInstruction::Transfer { lamports } => transfer(&accounts[0], &accounts[1], lamports),
+            SystemInstruction::CreateAccount { lamports, space, owner } => {
+                if accounts[1].lamports() > 0 {
+                    return Err(ProgramError::AccountAlreadyInitialized);
+                }
+                transfer(&accounts[0], &accounts[1], lamports)?;
+                allocate(&accounts[1], space)?;
+                accounts[1].assign(&owner);
+                Ok(())
+            }
+            SystemInstruction::Allocate { space } => allocate(&accounts[0], space),
+            SystemInstruction::Assign { owner } => {
+                system_owned(&accounts[0])?;
+                accounts[0].assign(&owner);
+                Ok(())
+            }
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+
+    /// Every account the test transactions can touch.
+    struct Ledger {
+        accounts: Vec<AccountInfo<'static>>,
+    }
+
+    impl Ledger {
+        /// A ledger holding the system and brood programs.
+        fn new() -> Self {
+            static INSTALL: std::sync::Once = std::sync::Once::new();
+            INSTALL.call_once(|| {
+                set_syscall_stubs(Box::new(TestRuntime));
+            });
+            let mut ledger = Ledger { accounts: Vec::new() };
+            for program in [system_program::ID, crate::ID] {
+                ledger.add(program, Pubkey::default(), 1, Vec::new());
+                ledger.accounts.last_mut().unwrap().executable = true;
+            }
+            ledger
+        }
+
+        fn add(&mut self, key: Pubkey, owner: Pubkey, lamports: u64, data: Vec<u8>) {
+            self.accounts.push(AccountInfo::new(
+                Box::leak(Box::new(key)),
+                false,
+                false,
+                Box::leak(Box::new(lamports)),
+                Box::leak(data.into_boxed_slice()),
+                Box::leak(Box::new(owner)),
+                false,
+                0,
+            ));
+        }
+
+        /// A system-owned wallet or PDA holding `lamports`.
+        fn add_wallet(&mut self, key: Pubkey, lamports: u64) {
+            self.add(key, system_program::ID, lamports, Vec::new());
+        }
+
+        /// A brood account holding `state`, padded to `space` bytes.
+        fn add_state<T: AccountSerialize>(&mut self, key: Pubkey, state: &T, space: usize) {
+            let mut data = Vec::with_capacity(space);
+            state.try_serialize(&mut data).unwrap();
+            data.resize(space, 0);
+            self.add(key, crate::ID, Rent::default().minimum_balance(space), data);
+        }
+
+        fn info(&self, key: &Pubkey) -> &AccountInfo<'static> {
+            self.accounts.iter().find(|info| info.key == key).unwrap()
+        }
+
+        fn lamports(&self, key: &Pubkey) -> u64 {
+            self.info(key).lamports()
+        }
+
+        fn state<T: AccountDeserialize>(&self, key: &Pubkey) -> T {
+            T::try_deserialize(&mut &self.info(key).try_borrow_data().unwrap()[..]).unwrap()
+        }
+
+        /// Run a top-level brood instruction; every account it marks as a
+        /// signer is taken to have signed.
+        fn execute(&self, accounts: impl ToAccountMetas, args: impl InstructionData) -> ProgramResult {
+            let infos: Vec<AccountInfo> = accounts
+                .to_account_metas(None)
+                .iter()
+                .map(|meta| {
+                    let mut info = self.info(&meta.pubkey).clone();
+                    info.is_signer = meta.is_signer;
+                    info.is_writable = meta.is_writable;
+                    info
+                })
+                .collect();
+            run_brood(&infos, &args.data())
+        }
+    }
+
+    fn pda(seeds: &[&[u8]]) -> (Pubkey, u8) {
+        Pubkey::find_program_address(seeds, &crate::ID)
+    }
+
+    /// A ledger with a config, an owner wallet and a mature, funded
+    /// gen-1 agent `parent` it owns.
+    struct World {
+        ledger: Ledger,
+        admin: Pubkey,
+        owner: Pubkey,
+        protocol_treasury: Pubkey,
+        parent: Pubkey,
+        parent_treasury: Pubkey,
+    }
+
+    const PARENT_TREASURY: u64 = 2_000_000_000;
+
+    fn world() -> World {
+        let mut ledger = Ledger::new();
+        let admin = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let protocol_treasury = Pubkey::new_unique();
+        ledger.add_wallet(owner, 10_000_000_000);
+        ledger.add_wallet(admin, 0);
+        ledger.add_wallet(protocol_treasury, 0);
+
+        let mut cfg = config();
+        cfg.admin = admin;
+        cfg.protocol_treasury = protocol_treasury;
+        cfg.spawn_fee = SPAWN_FEE;
+        cfg.min_operating_reserve = MIN_OPERATING_RESERVE;
+        cfg.max_agents_per_owner = MAX_AGENTS_PER_OWNER;
+        ledger.add_state(pda(&[b"config"]).0, &cfg, 8 + ProtocolConfig::INIT_SPACE);
+
+        let (parent, parent_treasury) = add_agent(&mut ledger, owner, "parent", |_| {});
+        World { ledger, admin, owner, protocol_treasury, parent, parent_treasury }
+    }
+
+    /// Add a mature agent `name` of `owner` holding `PARENT_TREASURY`,
+    /// adjusted by `customise`. Returns the agent and its treasury.
+    fn add_agent(
+        ledger: &mut Ledger,
+        owner: Pubkey,
+        name: &str,
+        customise: impl FnOnce(&mut Agent),
+    ) -> (Pubkey, Pubkey) {
+        let (key, agent_bump) = pda(&[b"agent", owner.as_ref(), name.as_bytes()]);
+        let (treasury, treasury_bump) = pda(&[b"treasury", key.as_ref()]);
+        let mut agent = unused_agent();
+        agent.init(key, owner, name.to_string(), [0; 32], String::new(), NOW - 86_400);
+        agent.agent_bump = agent_bump;
+        agent.treasury_bump = treasury_bump;
+        agent.treasury = PARENT_TREASURY;
+        agent.total_earnings = PARENT_TREASURY;
+        customise(&mut agent);
+        ledger.add_state(key, &agent, 8 + Agent::INIT_SPACE);
+        ledger.add_wallet(treasury, agent.treasury);
+        (key, treasury)
+    }
+
+    impl World {
+        /// Spawn `name` from the parent, creating every account spawn
+        /// initialises. Returns the child and its treasury.
+        fn spawn(&mut self, name: &str, seed_amount: u64) -> (ProgramResult, Pubkey, Pubkey) {
+            let child = pda(&[b"agent", self.owner.as_ref(), name.as_bytes()]).0;
+            let child_treasury = pda(&[b"treasury", child.as_ref()]).0;
+            let accounts = crate::accounts::Spawn {
+                parent_agent: self.parent,
+                child_agent: child,
+                parent_treasury: self.parent_treasury,
+                child_treasury,
+                parent_lineage: pda(&[b"lineage", self.parent.as_ref()]).0,
+                owner: self.owner,
+                system_program: system_program::ID,
+                config: pda(&[b"config"]).0,
+                grandparent_agent: None,
+                grandparent_treasury: None,
+                population: pda(&[b"population"]).0,
+                owner_stats: pda(&[b"owner", self.owner.as_ref()]).0,
+                brood: pda(&[b"brood", self.parent.as_ref()]).0,
+                protocol_treasury: self.protocol_treasury,
+                child_owner: None,
+            };
+            let created = [
+                child,
+                child_treasury,
+                accounts.parent_lineage,
+                accounts.population,
+                accounts.owner_stats,
+                accounts.brood,
+            ];
+            for key in created {
+                self.ledger.add_wallet(key, 0);
+            }
+            let args = crate::instruction::Spawn {
+                child_name: name.to_string(),
+                child_genome_hash: [1; 32],
+                child_genome_uri: "ipfs://child".to_string(),
+                seed_amount,
+            };
+            (self.ledger.execute(accounts, args), child, child_treasury)
+        }
+    }
+
+    #[test]
+    fn spawn_moves_the_seed_into_the_child_treasury_in_lamports() {
+        let mut w = world();
+        let seed = 500_000_000;
+        let (result, child, child_treasury) = w.spawn("child", seed);
+        result.unwrap();
+
+        assert_eq!(w.ledger.lamports(&child_treasury), seed);
+        assert_eq!(w.ledger.state::<Agent>(&child).treasury, seed);
+        assert_eq!(w.ledger.lamports(&w.parent_treasury), PARENT_TREASURY - seed - SPAWN_FEE);
+        assert_eq!(w.ledger.state::<Agent>(&w.parent).treasury, PARENT_TREASURY - seed - SPAWN_FEE);
+        assert_eq!(w.ledger.lamports(&w.protocol_treasury), SPAWN_FEE);
+    }
+
+    impl World {
+        /// Adopt `child` under `new_parent`, passing the lineages of the
+        /// child's current parent and co-parent.
+        fn adopt(&mut self, child: Pubkey, new_parent: Pubkey) -> ProgramResult {
+            let agent: Agent = self.ledger.state(&child);
+            let lineage = |parent: Option<Pubkey>| parent.map(|p| pda(&[b"lineage", p.as_ref()]).0);
+            let new_parent_lineage = pda(&[b"lineage", new_parent.as_ref()]).0;
+            self.ledger.add_wallet(new_parent_lineage, 0);
+            let accounts = crate::accounts::Adopt {
+                child,
+                new_parent,
+                new_parent_lineage,
+                child_owner: self.owner,
+                new_parent_owner: self.owner,
+                system_program: system_program::ID,
+                old_parent_lineage: lineage(agent.parent),
+                config: pda(&[b"config"]).0,
+                old_co_parent_lineage: lineage(agent.co_parent),
+            };
+            self.ledger.execute(accounts, crate::instruction::Adopt {})
+        }
+
+        /// Give `parent` a lineage account listing `children`.
+        fn add_lineage(&mut self, parent: Pubkey, children: Vec<Pubkey>) {
+            let key = pda(&[b"lineage", parent.as_ref()]).0;
+            self.ledger.add_state(key, &Lineage { children }, 8 + Lineage::INIT_SPACE);
+        }
+    }
+
+    #[test]
+    fn adopt_applies_the_new_lineage_generation_cap() {
+        let mut w = world();
+        let owner = w.owner;
+        let (capped, _) = add_agent(&mut w.ledger, owner, "capped", |a| {
+            a.generation = 3;
+            a.max_generation = 3;
+        });
+        let (orphan, _) = add_agent(&mut w.ledger, owner, "orphan", |_| {});
+        assert_eq!(w.adopt(orphan, capped), Err(Error::from(BroodError::MaxGenerationExceeded).into()));
+
+        let (roomy, _) = add_agent(&mut w.ledger, owner, "roomy", |a| {
+            a.generation = 3;
+            a.max_generation = 40;
+        });
+        w.adopt(orphan, roomy).unwrap();
+        let adopted: Agent = w.ledger.state(&orphan);
+        assert_eq!((adopted.generation, adopted.max_generation), (4, 40));
+    }
+
+    #[test]
+    fn adopting_a_bred_child_drops_its_co_parent() {
+        let mut w = world();
+        let owner = w.owner;
+        let (other, _) = add_agent(&mut w.ledger, owner, "other", |_| {});
+        let (parent, co_parent) = (w.parent, other);
+        let (bred, _) = add_agent(&mut w.ledger, owner, "bred", |a| {
+            a.parent = Some(parent);
+            a.co_parent = Some(co_parent);
+        });
+        w.add_lineage(parent, vec![bred]);
+        w.add_lineage(co_parent, vec![bred]);
+        let (new_parent, _) = add_agent(&mut w.ledger, owner, "new", |_| {});
+
+        w.adopt(bred, new_parent).unwrap();
+        let adopted: Agent = w.ledger.state(&bred);
+        assert_eq!((adopted.parent, adopted.co_parent), (Some(new_parent), None));
+        let children = |parent: Pubkey| {
+            w.ledger.state::<Lineage>(&pda(&[b"lineage", parent.as_ref()]).0).children
+        };
+        assert_eq!(children(new_parent), vec![bred]);
+        assert!(children(parent).is_empty());
+        assert!(children(co_parent).is_empty());
+    }
+
+    /// A program calling `record_earnings`, as the doc comment there
+    /// describes: it pays from a system-owned vault PDA it derives from
+    /// `[b"vault"]` and signs for with `invoke_signed`.
+    const EXAMPLE_CALLER: Pubkey = Pubkey::new_from_array([7; 32]);
+
+    fn pay_from_vault(w: &World, vault_bump: Option<u8>, amount: u64) -> ProgramResult {
+        let vault = Pubkey::find_program_address(&[b"vault"], &EXAMPLE_CALLER).0;
+        let accounts = crate::accounts::RecordEarnings {
+            agent: w.parent,
+            treasury: w.parent_treasury,
+            payer: vault,
+            system_program: system_program::ID,
+            config: pda(&[b"config"]).0,
+            protocol_treasury: w.protocol_treasury,
+            treasury_token_account: None,
+            payer_token_account: None,
+            token_program: None,
+            protocol_token_account: None,
+            payer_marker: None,
+            payment_policy: None,
+            treasury_history: None,
+            price_feed: None,
+        };
+        let instruction = Instruction {
+            program_id: crate::ID,
+            accounts: accounts.to_account_metas(None),
+            data: crate::instruction::RecordEarnings { amount }.data(),
+        };
+        // The caller was handed these writable by its own transaction
+        let infos: Vec<AccountInfo> = [
+            w.parent,
+            w.parent_treasury,
+            vault,
+            system_program::ID,
+            pda(&[b"config"]).0,
+            w.protocol_treasury,
+            crate::ID,
+        ]
+        .iter()
+        .map(|key| {
+            let mut info = w.ledger.info(key).clone();
+            info.is_writable = instruction.accounts.iter().any(|m| m.pubkey == *key && m.is_writable);
+            info
+        })
+        .collect();
+
+        let bump = vault_bump.map(|bump| [bump]);
+        let seeds: Vec<&[u8]> = match &bump {
+            Some(bump) => vec![b"vault", bump],
+            None => Vec::new(),
+        };
+        let signers: &[&[&[u8]]] = if seeds.is_empty() { &[] } else { &[&seeds] };
+        as_program(EXAMPLE_CALLER, || {
+            anchor_lang::solana_program::program::invoke_signed(&instruction, &infos, signers)
+        })
+    }
+
+    #[test]
+    fn a_program_pays_for_a_service_from_its_pda() {
+        let mut w = world();
+        let (vault, bump) = Pubkey::find_program_address(&[b"vault"], &EXAMPLE_CALLER);
+        w.ledger.add_wallet(vault, 1_000_000_000);
+
+        let amount = 10_000_000;
+        pay_from_vault(&w, Some(bump), amount).unwrap();
+
+        let fee = amount * PROTOCOL_FEE_BPS as u64 / 10_000;
+        assert_eq!(w.ledger.lamports(&vault), 1_000_000_000 - amount);
+        assert_eq!(w.ledger.lamports(&w.protocol_treasury), fee);
+        assert_eq!(w.ledger.lamports(&w.parent_treasury), PARENT_TREASURY + amount - fee);
+        let agent: Agent = w.ledger.state(&w.parent);
+        assert_eq!(agent.treasury, PARENT_TREASURY + amount - fee);
+        assert_eq!(agent.service_count, 1);
+    }
+
+    #[test]
+    fn a_program_must_sign_for_its_pda() {
+        let mut w = world();
+        let vault = Pubkey::find_program_address(&[b"vault"], &EXAMPLE_CALLER).0;
+        w.ledger.add_wallet(vault, 1_000_000_000);
+
+        assert_eq!(pay_from_vault(&w, None, 10_000_000), Err(ProgramError::MissingRequiredSignature));
+        assert_eq!(w.ledger.lamports(&vault), 1_000_000_000);
+    }
+
+    fn owner_stats(live: u32, total: u32) -> OwnerStats {
+        OwnerStats { owner: Pubkey::default(), live, total }
+    }
+
+    #[test]
+    fn transfers_move_the_live_count_but_not_the_total() {
+        let (mut from, mut to) = (owner_stats(3, 5), owner_stats(1, 1));
+        let receiver = Pubkey::new_unique();
+        count_transfer(&mut from, &mut to, receiver, 2).unwrap();
+        assert_eq!((from.live, from.total), (2, 5));
+        assert_eq!((to.owner, to.live, to.total), (receiver, 2, 1));
+    }
+
+    #[test]
+    fn transfers_respect_the_receivers_agent_limit() {
+        let (mut from, mut to) = (owner_stats(1, 1), owner_stats(2, 2));
+        assert_err(count_transfer(&mut from, &mut to, Pubkey::new_unique(), 2), BroodError::OwnerAgentLimit);
+        assert_eq!((from.live, to.live), (1, 2));
+        // Zero means unlimited
+        count_transfer(&mut from, &mut to, Pubkey::new_unique(), 0).unwrap();
+        assert_eq!(to.live, 3);
+    }
+
+    impl World {
+        fn emergency_pause(&self) -> ProgramResult {
+            let accounts = crate::accounts::EmergencyPause { config: pda(&[b"config"]).0, admin: self.admin };
+            self.ledger.execute(accounts, crate::instruction::EmergencyPause {})
+        }
+
+        fn withdraw(&self, amount: u64) -> ProgramResult {
+            let accounts = crate::accounts::WithdrawTreasury {
+                agent: self.parent,
+                treasury: self.parent_treasury,
+                owner: self.owner,
+                system_program: system_program::ID,
+                config: pda(&[b"config"]).0,
+                treasury_token_account: None,
+                owner_token_account: None,
+                token_program: None,
+            };
+            self.ledger.execute(accounts, crate::instruction::WithdrawTreasury { amount })
+        }
+    }
+
+    #[test]
+    fn emergency_pause_blocks_spawn_but_not_withdrawals() {
+        let mut w = world();
+        w.emergency_pause().unwrap();
+        assert!(w.ledger.state::<ProtocolConfig>(&pda(&[b"config"]).0).paused);
+
+        let (result, _, child_treasury) = w.spawn("child", 500_000_000);
+        assert_eq!(result, Err(Error::from(BroodError::ProgramPaused).into()));
+        assert_eq!(w.ledger.lamports(&child_treasury), 0);
+
+        let owner_before = w.ledger.lamports(&w.owner);
+        w.withdraw(100_000_000).unwrap();
+        assert_eq!(w.ledger.lamports(&w.owner), owner_before + 100_000_000);
+        assert_eq!(w.ledger.lamports(&w.parent_treasury), PARENT_TREASURY - 100_000_000);
+    }
 }
@@ -86,6 +86,62 @@ export class BroodClient {
     );
   }
 
+  getNameRegistryPDA(name: string): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("name"), Buffer.from(name)],
+      this.programId
+    );
+  }
+
+  getLineagePDA(agent: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("lineage"), agent.toBuffer()],
+      this.programId
+    );
+  }
+
+  getConfigPDA(): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("config")],
+      this.programId
+    );
+  }
+
+  getPolicyPDA(agent: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("policy"), agent.toBuffer()],
+      this.programId
+    );
+  }
+
+  getPopulationPDA(): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("population")],
+      this.programId
+    );
+  }
+
+  getOwnerStatsPDA(owner: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("owner"), owner.toBuffer()],
+      this.programId
+    );
+  }
+
+  getBroodPDA(root: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("brood"), root.toBuffer()],
+      this.programId
+    );
+  }
+
+  getPayerMarkerPDA(agent: PublicKey, payer: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("payer"), agent.toBuffer(), payer.toBuffer()],
+      this.programId
+    );
+  }
+
   // === Helpers ===
 
   hashGenome(genome: Genome): number[] {
@@ -94,21 +150,72 @@ export class BroodClient {
     return Array.from(hash);
   }
 
+  // Anchor treats the program ID as "not provided" for optional accounts
+  private none(count: number): web3.AccountMeta[] {
+    return Array.from({ length: count }, () => ({
+      pubkey: this.programId,
+      isSigner: false,
+      isWritable: false,
+    }));
+  }
+
+  // Protocol fees go to the treasury named in the config
+  private async getProtocolTreasury(): Promise<PublicKey> {
+    const [configPDA] = this.getConfigPDA();
+    const config = await this.provider.connection.getAccountInfo(configPDA);
+    if (!config) throw new Error("Protocol config not initialized");
+    return new PublicKey(config.data.subarray(8 + 32, 8 + 64));
+  }
+
+  // Follows parent links up to the bloodline root. An adopted agent's
+  // parent no longer leads to its birth root, so callers pass it instead.
+  private async findRoot(agent: PublicKey): Promise<PublicKey> {
+    let current = agent;
+    for (;;) {
+      const info = await this.provider.connection.getAccountInfo(current);
+      if (!info) throw new Error(`Agent ${current.toBase58()} not found`);
+      const parentOffset = 8 + 32 + 32;
+      if (info.data[parentOffset] !== 1) return current;
+      current = new PublicKey(info.data.subarray(parentOffset + 1, parentOffset + 33));
+    }
+  }
+
   private encodeString(s: string): Buffer {
     const len = Buffer.alloc(4);
     len.writeUInt32LE(s.length, 0);
     return Buffer.concat([len, Buffer.from(s)]);
   }
 
+  private encodeOptionU32(n?: number): Buffer {
+    if (n === undefined) return Buffer.from([0]);
+    const buf = Buffer.alloc(5);
+    buf.writeUInt8(1, 0);
+    buf.writeUInt32LE(n, 1);
+    return buf;
+  }
+
+  private encodeOptionI64(n?: number): Buffer {
+    if (n === undefined) return Buffer.from([0]);
+    const buf = Buffer.alloc(9);
+    buf.writeUInt8(1, 0);
+    buf.writeBigInt64LE(BigInt(n), 1);
+    return buf;
+  }
+
   // === Instructions (using raw transactions) ===
 
   async createAgent(
     name: string,
     genome: Genome,
-    genomeUri: string
+    genomeUri: string,
+    globalName: boolean = false,
+    maxGeneration?: number,
+    minPerformance?: number,
+    metadataUri?: string
   ): Promise<string> {
     const owner = this.provider.wallet.publicKey;
     const [agentPDA] = this.getAgentPDA(owner, name);
+    const [namePDA] = this.getNameRegistryPDA(name);
     const genomeHash = this.hashGenome(genome);
 
     // Build instruction data manually
@@ -120,6 +227,13 @@ export class BroodClient {
       this.encodeString(name),
       Buffer.from(genomeHash),
       this.encodeString(genomeUri),
+      Buffer.from([globalName ? 1 : 0]),
+      this.encodeOptionU32(maxGeneration),
+      this.encodeOptionI64(minPerformance),
+      metadataUri === undefined
+        ? Buffer.from([0])
+        : Buffer.concat([Buffer.from([1]), this.encodeString(metadataUri)]),
+      Buffer.from([0]), // cost_model: flat
     ]);
 
     const ix = new web3.TransactionInstruction({
@@ -127,6 +241,13 @@ export class BroodClient {
         { pubkey: agentPDA, isSigner: false, isWritable: true },
         { pubkey: owner, isSigner: true, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        { pubkey: this.getConfigPDA()[0], isSigner: false, isWritable: false },
+        ...this.none(1), // treasury_mint
+        ...(globalName
+          ? [{ pubkey: namePDA, isSigner: false, isWritable: true }]
+          : this.none(1)),
+        { pubkey: this.getPopulationPDA()[0], isSigner: false, isWritable: true },
+        { pubkey: this.getOwnerStatsPDA(owner)[0], isSigner: false, isWritable: true },
       ],
       programId: this.programId,
       data,
@@ -156,6 +277,8 @@ export class BroodClient {
         { pubkey: treasuryPDA, isSigner: false, isWritable: true },
         { pubkey: owner, isSigner: true, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        ...this.none(5), // token accounts, token program, sponsorship, history
+        { pubkey: this.getConfigPDA()[0], isSigner: false, isWritable: false },
       ],
       programId: this.programId,
       data,
@@ -171,13 +294,31 @@ export class BroodClient {
     childName: string,
     childGenome: Genome,
     childGenomeUri: string,
-    seedLamports: number
+    seedLamports: number,
+    root?: PublicKey,
+    childOwner?: Keypair // Must co-sign: the child counts against its agent limit
   ): Promise<string> {
     const owner = this.provider.wallet.publicKey;
+    const childOwnerKey = childOwner?.publicKey;
     const [parentPDA] = this.getAgentPDA(owner, parentName);
-    const [childPDA] = this.getAgentPDA(owner, childName);
+    const [childPDA] = this.getAgentPDA(childOwnerKey ?? owner, childName);
+    const [parentTreasuryPDA] = this.getTreasuryPDA(parentPDA);
+    const [childTreasuryPDA] = this.getTreasuryPDA(childPDA);
     const childGenomeHash = this.hashGenome(childGenome);
 
+    // Royalties go to the grandparent, so its accounts must be passed
+    const parentAgent = await this.getAgent(parentName);
+    const grandparentKeys = parentAgent?.parent
+      ? [
+          { pubkey: parentAgent.parent, isSigner: false, isWritable: true },
+          {
+            pubkey: this.getTreasuryPDA(parentAgent.parent)[0],
+            isSigner: false,
+            isWritable: true,
+          },
+        ]
+      : this.none(2);
+
     // Discriminator for spawn
     const discriminator = Buffer.from([17, 105, 240, 101, 4, 95, 45, 171]);
     
@@ -196,21 +337,46 @@ export class BroodClient {
       keys: [
         { pubkey: parentPDA, isSigner: false, isWritable: true },
         { pubkey: childPDA, isSigner: false, isWritable: true },
+        { pubkey: parentTreasuryPDA, isSigner: false, isWritable: true },
+        { pubkey: childTreasuryPDA, isSigner: false, isWritable: true },
+        { pubkey: this.getLineagePDA(parentPDA)[0], isSigner: false, isWritable: true },
         { pubkey: owner, isSigner: true, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        { pubkey: this.getConfigPDA()[0], isSigner: false, isWritable: false },
+        ...grandparentKeys,
+        { pubkey: this.getPopulationPDA()[0], isSigner: false, isWritable: true },
+        {
+          pubkey: this.getOwnerStatsPDA(childOwnerKey ?? owner)[0],
+          isSigner: false,
+          isWritable: true,
+        },
+        {
+          pubkey: this.getBroodPDA(root ?? (await this.findRoot(parentPDA)))[0],
+          isSigner: false,
+          isWritable: true,
+        },
+        { pubkey: await this.getProtocolTreasury(), isSigner: false, isWritable: true },
+        ...(childOwnerKey
+          ? [{ pubkey: childOwnerKey, isSigner: true, isWritable: false }]
+          : this.none(1)),
       ],
       programId: this.programId,
       data,
     });
 
     const tx = new web3.Transaction().add(ix);
-    const sig = await this.provider.sendAndConfirm(tx);
+    const sig = await this.provider.sendAndConfirm(tx, childOwner ? [childOwner] : []);
     return sig;
   }
 
-  async recordEarnings(name: string, amountLamports: number): Promise<string> {
+  async recordEarnings(
+    name: string,
+    amountLamports: number,
+    priceFeed?: PublicKey // Pyth SOL/USD feed, for agents priced in USD
+  ): Promise<string> {
     const owner = this.provider.wallet.publicKey;
     const [agentPDA] = this.getAgentPDA(owner, name);
+    const [treasuryPDA] = this.getTreasuryPDA(agentPDA);
 
     const discriminator = Buffer.from([217, 224, 183, 102, 227, 210, 189, 82]);
     
@@ -219,10 +385,29 @@ export class BroodClient {
 
     const data = Buffer.concat([discriminator, amountBuf]);
 
+    const protocolTreasury = await this.getProtocolTreasury();
+
+    // Agents with an allowlist require it on every payment
+    const [policyPDA] = this.getPolicyPDA(agentPDA);
+    const policy = await this.provider.connection.getAccountInfo(policyPDA);
+
     const ix = new web3.TransactionInstruction({
       keys: [
         { pubkey: agentPDA, isSigner: false, isWritable: true },
-        { pubkey: owner, isSigner: true, isWritable: false },
+        { pubkey: treasuryPDA, isSigner: false, isWritable: true },
+        { pubkey: owner, isSigner: true, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        { pubkey: this.getConfigPDA()[0], isSigner: false, isWritable: false },
+        { pubkey: protocolTreasury, isSigner: false, isWritable: true },
+        ...this.none(4), // token accounts, token program, protocol token account
+        { pubkey: this.getPayerMarkerPDA(agentPDA, owner)[0], isSigner: false, isWritable: true },
+        policy
+          ? { pubkey: policyPDA, isSigner: false, isWritable: false }
+          : this.none(1)[0],
+        ...this.none(1), // treasury_history
+        priceFeed
+          ? { pubkey: priceFeed, isSigner: false, isWritable: false }
+          : this.none(1)[0],
       ],
       programId: this.programId,
       data,
@@ -233,9 +418,14 @@ export class BroodClient {
     return sig;
   }
 
-  async deductCosts(name: string, amountLamports: number): Promise<string> {
+  async deductCosts(
+    name: string,
+    amountLamports: number,
+    costSink?: PublicKey
+  ): Promise<string> {
     const owner = this.provider.wallet.publicKey;
     const [agentPDA] = this.getAgentPDA(owner, name);
+    const [treasuryPDA] = this.getTreasuryPDA(agentPDA);
 
     const discriminator = Buffer.from([51, 116, 208, 108, 219, 210, 203, 39]);
     
@@ -247,7 +437,12 @@ export class BroodClient {
     const ix = new web3.TransactionInstruction({
       keys: [
         { pubkey: agentPDA, isSigner: false, isWritable: true },
+        { pubkey: treasuryPDA, isSigner: false, isWritable: true },
+        { pubkey: costSink || owner, isSigner: false, isWritable: true },
         { pubkey: owner, isSigner: true, isWritable: false },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        ...this.none(1), // treasury_history
+        { pubkey: this.getConfigPDA()[0], isSigner: false, isWritable: false },
       ],
       programId: this.programId,
       data,
@@ -279,6 +474,7 @@ export class BroodClient {
       keys: [
         { pubkey: agentPDA, isSigner: false, isWritable: true },
         { pubkey: owner, isSigner: true, isWritable: false },
+        { pubkey: this.getConfigPDA()[0], isSigner: false, isWritable: false },
       ],
       programId: this.programId,
       data,
@@ -289,6 +485,27 @@ export class BroodClient {
     return sig;
   }
 
+  async sealGenome(name: string): Promise<string> {
+    const owner = this.provider.wallet.publicKey;
+    const [agentPDA] = this.getAgentPDA(owner, name);
+
+    const discriminator = Buffer.from([157, 245, 37, 214, 122, 34, 22, 95]);
+
+    const ix = new web3.TransactionInstruction({
+      keys: [
+        { pubkey: agentPDA, isSigner: false, isWritable: true },
+        { pubkey: owner, isSigner: true, isWritable: false },
+        { pubkey: this.getConfigPDA()[0], isSigner: false, isWritable: false },
+      ],
+      programId: this.programId,
+      data: discriminator,
+    });
+
+    const tx = new web3.Transaction().add(ix);
+    const sig = await this.provider.sendAndConfirm(tx);
+    return sig;
+  }
+
   async killAgent(name: string): Promise<string> {
     const owner = this.provider.wallet.publicKey;
     const [agentPDA] = this.getAgentPDA(owner, name);
@@ -299,6 +516,7 @@ export class BroodClient {
       keys: [
         { pubkey: agentPDA, isSigner: false, isWritable: true },
         { pubkey: owner, isSigner: true, isWritable: false },
+        { pubkey: this.getConfigPDA()[0], isSigner: false, isWritable: false },
       ],
       programId: this.programId,
       data: discriminator,
@@ -402,7 +620,26 @@ export class BroodClient {
 
 // === Mutation Helpers ===
 
-export function mutateGenome(parent: Genome, mutationRate: number = 0.1): Genome {
+/**
+ * How far a mutated value strays from its parent. "uniform" is a flat
+ * ±20%; "triangular" averages two draws over the same range, so small
+ * tweaks are common and ±20% swings are rare.
+ */
+export type MutationModel = "uniform" | "triangular";
+
+/** Random offset in [-0.5, 0.5] under the given model */
+function mutationOffset(model: MutationModel): number {
+  if (model === "triangular") {
+    return (Math.random() + Math.random()) / 2 - 0.5;
+  }
+  return Math.random() - 0.5;
+}
+
+export function mutateGenome(
+  parent: Genome,
+  mutationRate: number = 0.1,
+  model: MutationModel = "uniform"
+): Genome {
   const child = JSON.parse(JSON.stringify(parent)) as Genome;
   const mutations: any[] = [];
 
@@ -410,7 +647,7 @@ export function mutateGenome(parent: Genome, mutationRate: number = 0.1): Genome
   if (child.settings) {
     for (const [key, value] of Object.entries(child.settings)) {
       if (typeof value === "number" && Math.random() < mutationRate) {
-        const delta = (Math.random() - 0.5) * 0.4 * value; // ±20%
+        const delta = mutationOffset(model) * 0.4 * value; // up to ±20%
         const newValue = Math.max(0, value + delta);
         (child.settings as any)[key] = newValue;
         mutations.push({
@@ -430,7 +667,7 @@ export function mutateGenome(parent: Genome, mutationRate: number = 0.1): Genome
     if (skill.params) {
       for (const [key, value] of Object.entries(skill.params)) {
         if (typeof value === "number" && Math.random() < 0.5) {
-          const delta = (Math.random() - 0.5) * 0.4 * value;
+          const delta = mutationOffset(model) * 0.4 * value;
           const newValue = Math.max(0, value + delta);
           skill.params[key] = newValue;
           mutations.push({
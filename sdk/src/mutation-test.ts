@@ -0,0 +1,57 @@
+import * as assert from "assert";
+import { Genome, MutationModel, mutateGenome } from "./index";
+
+// Statistical check that the triangular model keeps children closer to
+// their parent than the uniform one. No network needed.
+
+const PARENT_VALUE = 100;
+const TRIALS = 20_000;
+
+const parent: Genome = {
+  version: "1.0.0",
+  config: { model: "test", tools: [] },
+  skills: [],
+  settings: { risk_tolerance: PARENT_VALUE },
+};
+
+/** Relative change of every mutated child under `model` */
+function sampleDeviations(model: MutationModel): number[] {
+  const deviations: number[] = [];
+  for (let i = 0; i < TRIALS; i++) {
+    const child = mutateGenome(parent, 1, model);
+    const value = child.settings.risk_tolerance as number;
+    deviations.push((value - PARENT_VALUE) / PARENT_VALUE);
+  }
+  return deviations;
+}
+
+function main() {
+  const uniform = sampleDeviations("uniform");
+  const triangular = sampleDeviations("triangular");
+
+  for (const deviations of [uniform, triangular]) {
+    // Both models stay within the ±20% tweak range
+    assert.ok(deviations.every((d) => Math.abs(d) <= 0.2 + 1e-9));
+  }
+
+  // Expected share within ±5%: 25% uniform, ~44% triangular
+  const nearParent = (deviations: number[]) =>
+    deviations.filter((d) => Math.abs(d) <= 0.05).length / deviations.length;
+  const uniformNear = nearParent(uniform);
+  const triangularNear = nearParent(triangular);
+  assert.ok(Math.abs(uniformNear - 0.25) < 0.03, `uniform near share ${uniformNear}`);
+  assert.ok(triangularNear > uniformNear + 0.1, `triangular near share ${triangularNear}`);
+
+  // Both centred on the parent value
+  const mean = (deviations: number[]) =>
+    deviations.reduce((sum, d) => sum + d, 0) / deviations.length;
+  assert.ok(Math.abs(mean(uniform)) < 0.01);
+  assert.ok(Math.abs(mean(triangular)) < 0.01);
+
+  console.log(
+    `✓ within ±5% of parent: uniform ${(uniformNear * 100).toFixed(1)}%, ` +
+      `triangular ${(triangularNear * 100).toFixed(1)}%`
+  );
+}
+
+main();